@@ -5,13 +5,13 @@ use actix_web::{
     web::{post, resource, Data},
     App, HttpResponse, HttpServer,
 };
-use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form};
+use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form, UploadContext};
 use futures::Future;
 
 struct Gen;
 
 impl FilenameGenerator for Gen {
-    fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
+    fn next_filename(&self, _: &UploadContext) -> Option<PathBuf> {
         let mut p = PathBuf::new();
         p.push("examples/filename.png");
         Some(p)
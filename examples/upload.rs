@@ -1,8 +1,4 @@
-use std::{
-    env,
-    path::PathBuf,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use std::env;
 
 use actix_multipart::Multipart;
 use actix_web::{
@@ -16,26 +12,6 @@ use futures::Future;
 use log::info;
 use serde_derive::{Deserialize, Serialize};
 
-struct Gen(AtomicUsize);
-
-impl Gen {
-    pub fn new() -> Self {
-        Gen(AtomicUsize::new(0))
-    }
-}
-
-impl FilenameGenerator for Gen {
-    fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
-        let mut p = PathBuf::new();
-        p.push("examples");
-        p.push(&format!(
-            "filename{}.png",
-            self.0.fetch_add(1, Ordering::Relaxed)
-        ));
-        Some(p)
-    }
-}
-
 #[derive(Clone, Debug)]
 struct AppState {
     form: Form,
@@ -108,7 +84,10 @@ fn main() -> Result<(), failure::Error> {
                 .field("Two", Field::float())
                 .finalize(),
         )
-        .field("files", Field::array(Field::file(Gen::new())));
+        .field(
+            "files",
+            Field::array(Field::file(Generator::new("examples"))),
+        );
 
     info!("{:?}", form);
 
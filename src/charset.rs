@@ -0,0 +1,50 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::error::Error;
+
+#[cfg(feature = "encoding_rs")]
+fn decode_label(label: &str, bytes: &[u8]) -> Option<String> {
+    encoding_rs::Encoding::for_label(label.as_bytes())?
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn decode_label(label: &str, bytes: &[u8]) -> Option<String> {
+    match label.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "us-ascii" | "ascii" => {
+            Some(bytes.iter().map(|&b| char::from(b)).collect())
+        }
+        _ => None,
+    }
+}
+
+/// Decode text in the named charset, or UTF-8 if no charset is given
+///
+/// Without the `encoding_rs` feature, only UTF-8, ISO-8859-1, and US-ASCII are understood.
+pub(crate) fn decode(label: Option<&str>, bytes: &[u8]) -> Result<String, Error> {
+    let label = label.map(str::trim).unwrap_or("utf-8");
+
+    if label.eq_ignore_ascii_case("utf-8") || label.eq_ignore_ascii_case("utf8") {
+        return String::from_utf8(bytes.to_vec()).map_err(Error::ParseField);
+    }
+
+    decode_label(label, bytes).ok_or_else(|| Error::Charset(label.to_owned()))
+}
@@ -0,0 +1,99 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use actix_web::{http::HeaderMap, HttpRequest};
+
+use crate::types::NamePart;
+
+/// Everything known about an upload when a filename is generated for it
+///
+/// This is handed to `FilenameGenerator` and `AsyncFilenameGenerator` implementations for each
+/// file. The request is only available when the form is handled with
+/// `handle_multipart_request`.
+#[derive(Clone, Copy)]
+pub struct UploadContext<'a> {
+    path: &'a [NamePart],
+    filename: &'a str,
+    original_filename: &'a str,
+    content_type: &'a mime::Mime,
+    part_headers: &'a HeaderMap,
+    request: Option<&'a HttpRequest>,
+}
+
+impl<'a> UploadContext<'a> {
+    pub(crate) fn new(
+        path: &'a [NamePart],
+        filename: &'a str,
+        original_filename: &'a str,
+        content_type: &'a mime::Mime,
+        part_headers: &'a HeaderMap,
+        request: Option<&'a HttpRequest>,
+    ) -> Self {
+        UploadContext {
+            path,
+            filename,
+            original_filename,
+            content_type,
+            part_headers,
+            request,
+        }
+    }
+
+    /// The path to the field in the form, like `[Map("photos"), Array]` for `photos[]`
+    pub fn path(&self) -> &'a [NamePart] {
+        self.path
+    }
+
+    /// The client's filename after it's been through the form's `Sanitizer`
+    ///
+    /// This is the filename reported in `Value::File`.
+    pub fn filename(&self) -> &'a str {
+        self.filename
+    }
+
+    /// The filename exactly as the client sent it, which shouldn't be trusted
+    pub fn original_filename(&self) -> &'a str {
+        self.original_filename
+    }
+
+    /// The extension of the sanitized filename, without the leading dot
+    pub fn extension(&self) -> Option<&'a str> {
+        match self.filename.rfind('.') {
+            Some(pos) if pos > 0 && pos + 1 < self.filename.len() => {
+                Some(&self.filename[pos + 1..])
+            }
+            _ => None,
+        }
+    }
+
+    /// The `Content-Type` the client declared for the file
+    pub fn content_type(&self) -> &'a mime::Mime {
+        self.content_type
+    }
+
+    /// The headers of the multipart part containing the file
+    pub fn part_headers(&self) -> &'a HeaderMap {
+        self.part_headers
+    }
+
+    /// The request the file was uploaded in, for its headers and extensions
+    pub fn request(&self) -> Option<&'a HttpRequest> {
+        self.request
+    }
+}
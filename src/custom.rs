@@ -0,0 +1,129 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{any::Any, fmt, sync::Arc};
+
+use crate::{error::Error, types::MultipartContent, FieldParser};
+
+trait ErasedParser: Send + Sync {
+    fn parse_erased(&self, bytes: &[u8]) -> Result<CustomValue, failure::Error>;
+}
+
+impl<P> ErasedParser for P
+where
+    P: FieldParser,
+{
+    fn parse_erased(&self, bytes: &[u8]) -> Result<CustomValue, failure::Error> {
+        self.parse(bytes).map(CustomValue::new)
+    }
+}
+
+trait CustomAny: Any + fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &Any;
+}
+
+impl<T> CustomAny for T
+where
+    T: Any + fmt::Debug + Send + Sync,
+{
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+/// A definition of a field parsed by a user-provided `FieldParser`
+///
+/// The `Custom` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Clone)]
+pub struct Custom {
+    parser: Arc<ErasedParser>,
+}
+
+impl Custom {
+    pub(crate) fn new<P>(parser: P) -> Self
+    where
+        P: FieldParser + 'static,
+    {
+        Custom {
+            parser: Arc::new(parser),
+        }
+    }
+
+    pub(crate) fn parse(&self, name: &str, bytes: &[u8]) -> Result<MultipartContent, Error> {
+        self.parser
+            .parse_erased(bytes)
+            .map(MultipartContent::Custom)
+            .map_err(|e| Error::Custom(name.to_owned(), e))
+    }
+}
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Custom(field_parser)")
+    }
+}
+
+/// The output of a `FieldParser`, as stored in `Value::Custom`
+///
+/// Use `downcast_ref` with the parser's `Output` type to get at the parsed value. Two custom
+/// values are only equal if they are clones of the same parsed value.
+#[derive(Clone)]
+pub struct CustomValue {
+    inner: Arc<CustomAny>,
+}
+
+impl CustomValue {
+    fn new<T>(value: T) -> Self
+    where
+        T: Any + fmt::Debug + Send + Sync,
+    {
+        CustomValue {
+            inner: Arc::new(value),
+        }
+    }
+
+    /// Check whether the contained value is of type `T`
+    pub fn is<T>(&self) -> bool
+    where
+        T: Any,
+    {
+        (*self.inner).as_any().is::<T>()
+    }
+
+    /// Get a reference to the contained value, if it is of type `T`
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: Any,
+    {
+        (*self.inner).as_any().downcast_ref::<T>()
+    }
+}
+
+impl fmt::Debug for CustomValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl PartialEq for CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
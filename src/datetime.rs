@@ -0,0 +1,243 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, ParseResult, TimeZone, Utc};
+
+use crate::{error::Error, types::Field};
+
+fn formats(formats: &[&str]) -> Vec<String> {
+    formats.iter().map(|f| (*f).to_owned()).collect()
+}
+
+fn parse_with<T, F>(formats: &[String], value: &str, parse: F) -> Result<T, Error>
+where
+    F: Fn(&str, &str) -> ParseResult<T>,
+{
+    let value = value.trim();
+    let mut last_err = None;
+
+    for format in formats {
+        match parse(value, format) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err
+        .map(Error::ParseDateTime)
+        .unwrap_or(Error::FieldType))
+}
+
+/// A definition of a date field, such as an HTML `date` input
+///
+/// By default, dates are parsed in ISO-8601 form, e.g. `2019-06-28`.
+#[derive(Debug, Clone)]
+pub struct Date {
+    formats: Vec<String>,
+}
+
+impl Date {
+    pub(crate) fn new() -> Self {
+        Date {
+            formats: formats(&["%Y-%m-%d"]),
+        }
+    }
+
+    /// Set the `strftime`-style formats the date may be provided in
+    ///
+    /// Formats are tried in order, and the first one that matches is used.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::date().formats(&["%Y-%m-%d", "%d/%m/%Y"]);
+    /// ```
+    pub fn formats(mut self, formats: &[&str]) -> Self {
+        self.formats = self::formats(formats);
+
+        self
+    }
+
+    /// Finalize the date into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Date(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Result<NaiveDate, Error> {
+        parse_with(&self.formats, value, NaiveDate::parse_from_str)
+    }
+}
+
+/// A definition of a time field, such as an HTML `time` input
+///
+/// By default, times are parsed as `13:45`, `13:45:30`, or `13:45:30.125`.
+#[derive(Debug, Clone)]
+pub struct Time {
+    formats: Vec<String>,
+}
+
+impl Time {
+    pub(crate) fn new() -> Self {
+        Time {
+            formats: formats(&["%H:%M", "%H:%M:%S", "%H:%M:%S%.f"]),
+        }
+    }
+
+    /// Set the `strftime`-style formats the time may be provided in
+    ///
+    /// Formats are tried in order, and the first one that matches is used.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::time().formats(&["%I:%M %p"]);
+    /// ```
+    pub fn formats(mut self, formats: &[&str]) -> Self {
+        self.formats = self::formats(formats);
+
+        self
+    }
+
+    /// Finalize the time into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Time(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Result<NaiveTime, Error> {
+        parse_with(&self.formats, value, NaiveTime::parse_from_str)
+    }
+}
+
+/// A definition of a date and time field, such as an HTML `datetime-local` input
+///
+/// By default, values are parsed as `2019-06-28T13:45`, with optional seconds and fractional
+/// seconds. No timezone is expected.
+#[derive(Debug, Clone)]
+pub struct DateTime {
+    formats: Vec<String>,
+}
+
+impl DateTime {
+    pub(crate) fn new() -> Self {
+        DateTime {
+            formats: formats(&[
+                "%Y-%m-%dT%H:%M",
+                "%Y-%m-%dT%H:%M:%S",
+                "%Y-%m-%dT%H:%M:%S%.f",
+            ]),
+        }
+    }
+
+    /// Set the `strftime`-style formats the date and time may be provided in
+    ///
+    /// Formats are tried in order, and the first one that matches is used.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::datetime().formats(&["%Y-%m-%d %H:%M:%S"]);
+    /// ```
+    pub fn formats(mut self, formats: &[&str]) -> Self {
+        self.formats = self::formats(formats);
+
+        self
+    }
+
+    /// Finalize the date and time into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::DateTime(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Result<NaiveDateTime, Error> {
+        parse_with(&self.formats, value, NaiveDateTime::parse_from_str)
+    }
+}
+
+/// A definition of a Unix timestamp field
+///
+/// By default, timestamps are parsed as whole seconds since the Unix epoch.
+#[derive(Debug, Clone)]
+pub struct Timestamp {
+    millis: bool,
+}
+
+impl Timestamp {
+    pub(crate) fn new() -> Self {
+        Timestamp { millis: false }
+    }
+
+    /// Parse timestamps as milliseconds since the Unix epoch, as produced by Javascript's
+    /// `Date.now()`
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::timestamp().millis();
+    /// ```
+    pub fn millis(mut self) -> Self {
+        self.millis = true;
+
+        self
+    }
+
+    /// Finalize the timestamp into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Timestamp(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Result<chrono::DateTime<Utc>, Error> {
+        let int = value.trim().parse::<i64>().map_err(Error::ParseInt)?;
+
+        let parsed = if self.millis {
+            Utc.timestamp_millis_opt(int)
+        } else {
+            Utc.timestamp_opt(int, 0)
+        };
+
+        parsed.single().ok_or(Error::TimestampRange)
+    }
+}
+
+impl From<Date> for Field {
+    fn from(date: Date) -> Self {
+        date.finalize()
+    }
+}
+
+impl From<Time> for Field {
+    fn from(time: Time) -> Self {
+        time.finalize()
+    }
+}
+
+impl From<DateTime> for Field {
+    fn from(datetime: DateTime) -> Self {
+        datetime.finalize()
+    }
+}
+
+impl From<Timestamp> for Field {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.finalize()
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::{
+    de::{
+        value::{Error, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+
+use crate::types::Value;
+
+/// Deserialize a parsed `Value` into any type implementing `serde::Deserialize`
+///
+/// Maps become structs or maps, arrays become sequences, and files become structs with
+/// `filename`, `path`, and `size` fields. Text values can be deserialized into unit enum
+/// variants, which pairs well with `Field::choice`, and indexed choices deserialize into enum
+/// variants by position.
+///
+/// # Example
+/// ```rust
+/// # use form_data::{from_value, Value, ValueMap};
+/// # use serde_derive::Deserialize;
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "lowercase")]
+/// enum Status {
+///     Draft,
+///     Published,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Post {
+///     status: Status,
+/// }
+///
+/// let mut hm = ValueMap::new();
+/// hm.insert("status".to_owned(), Value::Text("published".to_owned()));
+///
+/// let post: Post = from_value(Value::Map(hm)).unwrap();
+/// ```
+pub fn from_value<T>(value: Value) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Map(hm) => {
+                let mut map = MapDeserializer::new(hm.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Array(vec) => {
+                let mut seq = SeqDeserializer::new(vec.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::File {
+                filename,
+                stored_as,
+                size,
+            } => {
+                let entries = vec![
+                    ("filename", Value::Text(filename)),
+                    (
+                        "path",
+                        Value::Text(stored_as.to_string_lossy().into_owned()),
+                    ),
+                    ("size", Value::Int(size as i64)),
+                ];
+                let mut map = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Text(text) => visitor.visit_string(text),
+            Value::Int(int) => visitor.visit_i64(int),
+            Value::Float(float) => visitor.visit_f64(float),
+            Value::Bool(b) => visitor.visit_bool(b),
+            #[cfg(feature = "chrono")]
+            Value::Date(date) => visitor.visit_string(date.to_string()),
+            #[cfg(feature = "chrono")]
+            Value::Time(time) => visitor.visit_string(time.to_string()),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(datetime) => visitor.visit_string(datetime.to_string()),
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(timestamp) => visitor.visit_string(timestamp.to_rfc3339()),
+            #[cfg(feature = "serde_json")]
+            Value::Json(json) => json
+                .deserialize_any(visitor)
+                .map_err(serde::de::Error::custom),
+            Value::Custom(_) => Err(serde::de::Error::custom(
+                "custom values cannot be deserialized",
+            )),
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes.to_vec()),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(text) => visitor.visit_enum(text.into_deserializer()),
+            Value::Int(int) if int >= 0 && int <= i64::from(u32::MAX) => {
+                visitor.visit_enum((int as u32).into_deserializer())
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
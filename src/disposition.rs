@@ -0,0 +1,332 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use url::percent_encoding::percent_decode;
+
+use crate::{charset, types::ContentDisposition};
+
+struct Param {
+    name: String,
+    section: Option<usize>,
+    extended: bool,
+    value: Vec<u8>,
+}
+
+impl Param {
+    fn new(name: &str, value: Vec<u8>) -> Self {
+        let name = name.trim().to_ascii_lowercase();
+
+        let (name, extended) = if name.ends_with('*') {
+            (name[..name.len() - 1].to_owned(), true)
+        } else {
+            (name, false)
+        };
+
+        let section = name.rfind('*').and_then(|pos| {
+            let digits = &name[pos + 1..];
+
+            if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse().ok().map(|section| (pos, section))
+            } else {
+                None
+            }
+        });
+
+        match section {
+            Some((pos, section)) => Param {
+                name: name[..pos].to_owned(),
+                section: Some(section),
+                extended,
+                value,
+            },
+            None => Param {
+                name,
+                section: None,
+                extended,
+                value,
+            },
+        }
+    }
+}
+
+/// Split the header into its parameters, skipping the disposition type
+fn split_params(header: &[u8]) -> Vec<Param> {
+    let mut params = Vec::new();
+    let mut i = match header.iter().position(|&b| b == b';') {
+        Some(pos) => pos + 1,
+        None => return params,
+    };
+
+    while i < header.len() {
+        while i < header.len() && (header[i] == b';' || header[i].is_ascii_whitespace()) {
+            i += 1;
+        }
+
+        let start = i;
+        while i < header.len() && header[i] != b'=' && header[i] != b';' {
+            i += 1;
+        }
+
+        let name = String::from_utf8_lossy(&header[start..i]).into_owned();
+
+        if i >= header.len() || header[i] == b';' {
+            continue;
+        }
+
+        // Skip the '='
+        i += 1;
+
+        while i < header.len() && header[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let mut value = Vec::new();
+
+        if i < header.len() && header[i] == b'"' {
+            i += 1;
+
+            while i < header.len() && header[i] != b'"' {
+                if header[i] == b'\\' && i + 1 < header.len() {
+                    i += 1;
+                }
+
+                value.push(header[i]);
+                i += 1;
+            }
+
+            // Skip the closing quote
+            i += 1;
+        } else {
+            while i < header.len() && header[i] != b';' {
+                value.push(header[i]);
+                i += 1;
+            }
+
+            while value
+                .last()
+                .map(|b| b.is_ascii_whitespace())
+                .unwrap_or(false)
+            {
+                value.pop();
+            }
+        }
+
+        params.push(Param::new(&name, value));
+    }
+
+    params
+}
+
+fn decode_charset(label: &str, bytes: Vec<u8>) -> Option<String> {
+    charset::decode(Some(label), &bytes).ok()
+}
+
+/// Decode a plain value, which should be ASCII but is often sent as raw UTF-8
+fn decode_plain(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|e| e.into_bytes().into_iter().map(char::from).collect())
+}
+
+/// Split `charset'language'value` into the charset and the value
+fn split_extended(value: &[u8]) -> Option<(String, &[u8])> {
+    let first = value.iter().position(|&b| b == b'\'')?;
+    let second = value[first + 1..].iter().position(|&b| b == b'\'')? + first + 1;
+
+    let charset = String::from_utf8_lossy(&value[..first]).into_owned();
+
+    Some((charset, &value[second + 1..]))
+}
+
+fn extended_value(params: &[Param], name: &str) -> Option<String> {
+    let param = params
+        .iter()
+        .find(|p| p.name == name && p.extended && p.section.is_none())?;
+
+    let (charset, value) = split_extended(&param.value)?;
+
+    decode_charset(&charset, percent_decode(value).collect())
+}
+
+fn continued_value(params: &[Param], name: &str) -> Option<String> {
+    let mut sections: Vec<&Param> = params
+        .iter()
+        .filter(|p| p.name == name && p.section.is_some())
+        .collect();
+
+    if sections.is_empty() {
+        return None;
+    }
+
+    sections.sort_by_key(|p| p.section);
+
+    let mut charset = None;
+    let mut bytes = Vec::new();
+
+    // Sections must count up from zero, anything after a gap is ignored
+    for (expected, param) in sections.into_iter().enumerate() {
+        if param.section != Some(expected) {
+            break;
+        }
+
+        if !param.extended {
+            bytes.extend_from_slice(&param.value);
+        } else if expected == 0 {
+            let (cs, value) = split_extended(&param.value)?;
+            charset = Some(cs);
+            bytes.extend(percent_decode(value));
+        } else {
+            bytes.extend(percent_decode(&param.value));
+        }
+    }
+
+    match charset {
+        Some(charset) => decode_charset(&charset, bytes),
+        None => Some(decode_plain(bytes)),
+    }
+}
+
+fn plain_value(params: &[Param], name: &str) -> Option<String> {
+    params
+        .iter()
+        .find(|p| p.name == name && !p.extended && p.section.is_none())
+        .map(|p| decode_plain(p.value.clone()))
+}
+
+fn param_value(params: &[Param], name: &str) -> Option<String> {
+    extended_value(params, name)
+        .or_else(|| continued_value(params, name))
+        .or_else(|| plain_value(params, name))
+}
+
+/// Parse the `name` and `filename` out of a `Content-Disposition` header
+///
+/// Besides plain parameters, this understands the extended parameters of RFC 5987
+/// (`filename*=UTF-8''%E5%86%99%E7%9C%9F.png`) and the continuations of RFC 2231
+/// (`filename*0*=UTF-8''...; filename*1*=...`). Extended values take precedence over plain
+/// ones, which are kept as a fallback for when the extended value can't be decoded.
+pub(crate) fn parse(header: &[u8]) -> ContentDisposition {
+    let params = split_params(header);
+
+    ContentDisposition {
+        name: param_value(&params, "name"),
+        filename: param_value(&params, "filename"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn filenames() {
+        let cases: Vec<(&str, Option<&str>)> = vec![
+            ("form-data; name=\"a\"", None),
+            (
+                "form-data; name=\"a\"; filename=\"photo.png\"",
+                Some("photo.png"),
+            ),
+            ("form-data; name=a; filename=photo.png", Some("photo.png")),
+            ("form-data; name=\"a\"; filename=\"\"", Some("")),
+            (
+                "form-data; name=\"a\"; filename=\"say \\\"hi\\\".txt\"",
+                Some("say \"hi\".txt"),
+            ),
+            (
+                "form-data; name=\"a\"; filename=\"a;b.txt\"",
+                Some("a;b.txt"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*=UTF-8''%E5%86%99%E7%9C%9F.png",
+                Some("写真.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*=utf-8'en'%E5%86%99%E7%9C%9F.png",
+                Some("写真.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*=ISO-8859-1''caf%E9.txt",
+                Some("café.txt"),
+            ),
+            (
+                "form-data; name=\"a\"; filename=\"photo.png\"; \
+                 filename*=UTF-8''%E5%86%99%E7%9C%9F.png",
+                Some("写真.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*=UTF-8''%E5%86%99%E7%9C%9F.png; \
+                 filename=\"photo.png\"",
+                Some("写真.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*=UTF-8''100%zz.png",
+                Some("100%zz.png"),
+            ),
+            ("form-data; name=\"a\"; filename*=UTF-8''%E5%8.png", None),
+            (
+                "form-data; name=\"a\"; filename=\"photo.png\"; filename*=UTF-8''%FF.png",
+                Some("photo.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename=\"photo.png\"; filename*=%E5%86%99.png",
+                Some("photo.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*0*=UTF-8''%E5%86%99; filename*1=.png",
+                Some("写.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*1=.png; filename*0=photo",
+                Some("photo.png"),
+            ),
+            (
+                "form-data; name=\"a\"; filename*0=photo; filename*2=.png",
+                Some("photo"),
+            ),
+            (
+                "form-data; name=\"a\"; filename=\"写真.png\"",
+                Some("写真.png"),
+            ),
+        ];
+
+        for (header, expected) in cases {
+            let disposition = parse(header.as_bytes());
+
+            assert_eq!(disposition.name.as_deref(), Some("a"), "{}", header);
+            assert_eq!(disposition.filename.as_deref(), expected, "{}", header);
+        }
+    }
+
+    #[test]
+    fn names() {
+        let cases: Vec<(&str, Option<&str>)> = vec![
+            ("form-data", None),
+            ("form-data; name=\"user[name]\"", Some("user[name]")),
+            ("form-data; NAME=\"a\"", Some("a")),
+            ("form-data;name=a", Some("a")),
+            ("form-data; name*=UTF-8''%C3%A9t%C3%A9", Some("été")),
+            ("form-data; filename=\"a.txt\"", None),
+        ];
+
+        for (header, expected) in cases {
+            let disposition = parse(header.as_bytes());
+
+            assert_eq!(disposition.name.as_deref(), expected, "{}", header);
+        }
+    }
+}
@@ -18,7 +18,7 @@
  */
 
 use std::{
-    io,
+    fmt, io,
     num::{ParseFloatError, ParseIntError},
     string::FromUtf8Error,
 };
@@ -46,8 +46,35 @@ pub enum Error {
     ParseInt(#[cause] ParseIntError),
     #[fail(display = "Failed to parse float, {}", _0)]
     ParseFloat(#[cause] ParseFloatError),
+    #[fail(display = "Failed to parse bool")]
+    ParseBool,
+    #[fail(display = "Failed to parse base64, {}", _0)]
+    ParseBase64(#[cause] base64::DecodeError),
+    #[fail(display = "Failed to decode text as '{}'", _0)]
+    Charset(String),
+    #[fail(display = "Unsupported Content-Transfer-Encoding '{}'", _0)]
+    TransferEncoding(String),
+    #[fail(display = "Invalid choice '{}', expected one of: {}", _0, _1)]
+    Choice(String, String),
+    #[fail(display = "Field '{}' failed constraint {}", _0, _1)]
+    Constraint(String, Constraint),
+    #[fail(display = "Failed to parse field '{}', {}", _0, _1)]
+    Custom(String, failure::Error),
+    #[fail(display = "{}", _0)]
+    Validation(Box<ResponseError + Send + Sync>),
+    #[cfg(feature = "chrono")]
+    #[fail(display = "Failed to parse date or time, {}", _0)]
+    ParseDateTime(#[cause] chrono::ParseError),
+    #[cfg(feature = "chrono")]
+    #[fail(display = "Timestamp out of range")]
+    TimestampRange,
+    #[cfg(feature = "serde_json")]
+    #[fail(display = "Failed to parse json, {}", _0)]
+    ParseJson(#[cause] serde_json::Error),
     #[fail(display = "Failed to generate filename")]
     GenFilename,
+    #[fail(display = "Generated path is outside the storage root")]
+    StorageRoot,
     #[fail(display = "Bad Content-Type")]
     ContentType,
     #[fail(display = "Bad Content-Disposition")]
@@ -60,8 +87,16 @@ pub enum Error {
     WriteFile,
     #[fail(display = "Too many fields in request")]
     FieldCount,
+    #[fail(display = "Too many keys in map")]
+    KeyCount,
+    #[fail(display = "Field '{}' was submitted more than once", _0)]
+    DuplicateField(String),
+    #[fail(display = "Field '{}' conflicts with an earlier field", _0)]
+    FieldShape(String),
     #[fail(display = "Field too large")]
     FieldSize,
+    #[fail(display = "Request body too large")]
+    BodySize,
     #[fail(display = "Found field with unexpected name or type")]
     FieldType,
     #[fail(display = "Failed to parse filename")]
@@ -72,6 +107,48 @@ pub enum Error {
     FileSize,
 }
 
+/// A description of a constraint placed on a field
+///
+/// This is provided by `Error::Constraint` along with the name of the offending field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    MinInt(i64),
+    MaxInt(i64),
+    MinFloat(f64),
+    MaxFloat(f64),
+    MinLen(usize),
+    MaxLen(usize),
+    Pattern(String),
+    NonEmpty,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constraint::MinInt(min) => write!(f, "min({})", min),
+            Constraint::MaxInt(max) => write!(f, "max({})", max),
+            Constraint::MinFloat(min) => write!(f, "min({})", min),
+            Constraint::MaxFloat(max) => write!(f, "max({})", max),
+            Constraint::MinLen(min) => write!(f, "min_len({})", min),
+            Constraint::MaxLen(max) => write!(f, "max_len({})", max),
+            Constraint::Pattern(ref pattern) => write!(f, "pattern({})", pattern),
+            Constraint::NonEmpty => write!(f, "non_empty"),
+        }
+    }
+}
+
+impl Error {
+    /// Create an error for rejecting a form from a validator
+    ///
+    /// The provided error's response is used when this error is returned from a handler.
+    pub fn validation<E>(e: E) -> Self
+    where
+        E: ResponseError + Send + Sync + 'static,
+    {
+        Error::Validation(Box::new(e))
+    }
+}
+
 impl From<MultipartError> for Error {
     fn from(e: MultipartError) -> Self {
         Error::Multipart(e)
@@ -102,16 +179,34 @@ impl ResponseError for Error {
             Error::FsPool(_) => HttpResponse::InternalServerError().finish(),
             Error::Payload(ref e) => ResponseError::error_response(e),
             Error::Multipart(ref e) => ResponseError::error_response(e),
-            Error::ParseField(_) | Error::ParseInt(_) | Error::ParseFloat(_) => {
-                HttpResponse::BadRequest().finish()
+            Error::ParseField(_)
+            | Error::ParseInt(_)
+            | Error::ParseFloat(_)
+            | Error::ParseBool
+            | Error::ParseBase64(_)
+            | Error::Charset(_)
+            | Error::TransferEncoding(_)
+            | Error::Choice(_, _)
+            | Error::Constraint(_, _)
+            | Error::Custom(_, _) => HttpResponse::BadRequest().finish(),
+            #[cfg(feature = "chrono")]
+            Error::ParseDateTime(_) | Error::TimestampRange => HttpResponse::BadRequest().finish(),
+            #[cfg(feature = "serde_json")]
+            Error::ParseJson(_) => HttpResponse::BadRequest().finish(),
+            Error::Validation(ref e) => e.error_response(),
+            Error::GenFilename | Error::StorageRoot | Error::MkDir => {
+                HttpResponse::InternalServerError().finish()
             }
-            Error::GenFilename | Error::MkDir => HttpResponse::InternalServerError().finish(),
             Error::ContentType
             | Error::ContentDisposition
             | Error::Field
             | Error::FieldCount
+            | Error::KeyCount
+            | Error::DuplicateField(_)
+            | Error::FieldShape(_)
             | Error::WriteFile
             | Error::FieldSize
+            | Error::BodySize
             | Error::FieldType
             | Error::Filename
             | Error::FileCount
@@ -1,7 +1,10 @@
 use bytes::{Bytes, BytesMut};
 use failure::Fail;
 use futures::{
-    sync::mpsc::{channel, SendError},
+    sync::{
+        mpsc::{channel, SendError},
+        oneshot,
+    },
     task, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream,
 };
 use std::{
@@ -14,10 +17,18 @@ use std::{
 #[fail(display = "Error in Channel")]
 struct ChannelError;
 
+/// Write a file on the threadpool, returning a sink for its contents and a receiver that's
+/// completed once the whole file is written
+///
+/// The receiver is canceled if writing fails.
 pub fn write(
     filename: impl AsRef<Path> + Clone + Send + 'static,
-) -> impl Sink<SinkItem = Bytes, SinkError = SendError<Bytes>> {
+) -> (
+    impl Sink<SinkItem = Bytes, SinkError = SendError<Bytes>>,
+    oneshot::Receiver<()>,
+) {
     let (tx, rx) = channel(50);
+    let (done_tx, done_rx) = oneshot::channel();
 
     actix_rt::spawn(
         actix_threadpool::run(move || {
@@ -30,10 +41,12 @@ pub fn write(
                 .wait()
         })
         .map_err(|_| ())
-        .map(|_| ()),
+        .map(move |_| {
+            let _ = done_tx.send(());
+        }),
     );
 
-    tx
+    (tx, done_rx)
 }
 
 struct CreateFuture<P>(P)
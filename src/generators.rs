@@ -0,0 +1,318 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "sha2")]
+use std::path::Path;
+use std::{
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{context::UploadContext, FilenameGenerator};
+
+/// Extensions for common media types, the first listed for a type is the one used
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("application/gzip", "gz"),
+    ("application/json", "json"),
+    ("application/msword", "doc"),
+    ("application/octet-stream", "bin"),
+    ("application/ogg", "ogx"),
+    ("application/pdf", "pdf"),
+    ("application/rtf", "rtf"),
+    ("application/vnd.ms-excel", "xls"),
+    ("application/vnd.ms-powerpoint", "ppt"),
+    ("application/vnd.oasis.opendocument.presentation", "odp"),
+    ("application/vnd.oasis.opendocument.spreadsheet", "ods"),
+    ("application/vnd.oasis.opendocument.text", "odt"),
+    (
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "pptx",
+    ),
+    (
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "xlsx",
+    ),
+    (
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "docx",
+    ),
+    ("application/x-7z-compressed", "7z"),
+    ("application/x-bzip2", "bz2"),
+    ("application/x-gzip", "gz"),
+    ("application/x-tar", "tar"),
+    ("application/xml", "xml"),
+    ("application/zip", "zip"),
+    ("audio/aac", "aac"),
+    ("audio/flac", "flac"),
+    ("audio/mp4", "m4a"),
+    ("audio/mpeg", "mp3"),
+    ("audio/ogg", "ogg"),
+    ("audio/opus", "opus"),
+    ("audio/wav", "wav"),
+    ("audio/webm", "weba"),
+    ("audio/x-wav", "wav"),
+    ("font/otf", "otf"),
+    ("font/ttf", "ttf"),
+    ("font/woff", "woff"),
+    ("font/woff2", "woff2"),
+    ("image/avif", "avif"),
+    ("image/bmp", "bmp"),
+    ("image/gif", "gif"),
+    ("image/heic", "heic"),
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/svg+xml", "svg"),
+    ("image/tiff", "tiff"),
+    ("image/vnd.microsoft.icon", "ico"),
+    ("image/webp", "webp"),
+    ("image/x-icon", "ico"),
+    ("text/calendar", "ics"),
+    ("text/css", "css"),
+    ("text/csv", "csv"),
+    ("text/html", "html"),
+    ("text/javascript", "js"),
+    ("text/markdown", "md"),
+    ("text/plain", "txt"),
+    ("text/xml", "xml"),
+    ("video/mp4", "mp4"),
+    ("video/mpeg", "mpeg"),
+    ("video/ogg", "ogv"),
+    ("video/quicktime", "mov"),
+    ("video/webm", "webm"),
+    ("video/x-msvideo", "avi"),
+];
+
+/// Find the usual file extension for a media type, ignoring its parameters
+///
+/// # Example
+/// ```rust
+/// # use form_data::mime_extension;
+/// #
+/// assert_eq!(mime_extension(&mime::IMAGE_PNG), Some("png"));
+/// assert_eq!(mime_extension(&"text/plain; charset=utf-8".parse().unwrap()), Some("txt"));
+/// ```
+pub fn mime_extension(mime_type: &mime::Mime) -> Option<&'static str> {
+    let essence = format!("{}/{}", mime_type.type_(), mime_type.subtype());
+
+    EXTENSIONS
+        .iter()
+        .find(|&&(name, _)| essence.eq_ignore_ascii_case(name))
+        .map(|&(_, extension)| extension)
+}
+
+/// How a `Generator` names files
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Naming {
+    /// Names made from the current time, the process id, and a counter, which don't need any
+    /// extra features
+    Unique,
+    /// Random version 4 UUIDs
+    #[cfg(feature = "uuid")]
+    Uuid,
+    /// The hex encoded SHA-256 digest of the file's contents
+    ///
+    /// Files are written under a temporary name and moved once they're complete, so identical
+    /// uploads end up at the same path.
+    #[cfg(feature = "sha2")]
+    ContentHash,
+}
+
+/// Which extension a `Generator` gives files
+#[derive(Clone, Debug, PartialEq)]
+pub enum Extension {
+    /// The usual extension for the part's `Content-Type`, as found by `mime_extension`
+    FromMime,
+    /// The extension of the client's filename, after it's been sanitized
+    Original,
+    /// The same extension for every file
+    Fixed(String),
+    /// No extension
+    None,
+}
+
+/// A `FilenameGenerator` assembled from a directory, a naming strategy, and an extension
+///
+/// By default, files get `Unique` names with the extension for their media type, directly in
+/// the given directory.
+///
+/// # Example
+/// ```rust
+/// # use form_data::{Extension, Field, Form, Generator};
+/// #
+/// let form = Form::new().field(
+///     "avatar",
+///     Field::file(
+///         Generator::new("uploads/avatars")
+///             .prefix("avatar-")
+///             .extension(Extension::Fixed("png".to_owned())),
+///     ),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Generator {
+    dir: PathBuf,
+    prefix: String,
+    naming: Naming,
+    extension: Extension,
+    #[cfg(feature = "chrono")]
+    sharded: bool,
+    counter: AtomicUsize,
+}
+
+impl Generator {
+    /// Create a generator that stores files in `dir`
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Generator {
+            dir: dir.into(),
+            prefix: String::new(),
+            naming: Naming::Unique,
+            extension: Extension::FromMime,
+            #[cfg(feature = "chrono")]
+            sharded: false,
+            counter: AtomicUsize::new(0),
+        }
+    }
+
+    /// Create a generator that stores files in `dir`, named with random UUIDs
+    #[cfg(feature = "uuid")]
+    pub fn uuid<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Generator::new(dir).naming(Naming::Uuid)
+    }
+
+    /// Create a generator that stores files in `dir`, named after their contents
+    #[cfg(feature = "sha2")]
+    pub fn content_hash<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Generator::new(dir).naming(Naming::ContentHash)
+    }
+
+    /// Set a prefix for every filename
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+
+        self
+    }
+
+    /// Set how files are named
+    pub fn naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+
+        self
+    }
+
+    /// Set which extension files are given
+    pub fn extension(mut self, extension: Extension) -> Self {
+        self.extension = extension;
+
+        self
+    }
+
+    /// Store files in `yyyy/mm/dd` directories for the day they were uploaded, in UTC
+    #[cfg(feature = "chrono")]
+    pub fn sharded(mut self) -> Self {
+        self.sharded = true;
+
+        self
+    }
+
+    #[cfg(feature = "chrono")]
+    fn directory(&self) -> PathBuf {
+        if self.sharded {
+            self.dir
+                .join(chrono::Utc::now().format("%Y/%m/%d").to_string())
+        } else {
+            self.dir.clone()
+        }
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    fn directory(&self) -> PathBuf {
+        self.dir.clone()
+    }
+
+    fn unique(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        format!(
+            "{}{:09}-{}-{}",
+            now.as_secs(),
+            now.subsec_nanos(),
+            process::id(),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// The extension for a file, including its leading dot
+    fn suffix(&self, ctx: &UploadContext) -> String {
+        let extension = match self.extension {
+            Extension::FromMime => mime_extension(ctx.content_type()),
+            Extension::Original => ctx.extension(),
+            Extension::Fixed(ref extension) => Some(extension.as_str()),
+            Extension::None => None,
+        };
+
+        extension
+            .map(|extension| format!(".{}", extension))
+            .unwrap_or_default()
+    }
+}
+
+impl FilenameGenerator for Generator {
+    fn next_filename(&self, ctx: &UploadContext) -> Option<PathBuf> {
+        let suffix = self.suffix(ctx);
+
+        let name = match self.naming {
+            Naming::Unique => format!("{}{}{}", self.prefix, self.unique(), suffix),
+            #[cfg(feature = "uuid")]
+            Naming::Uuid => format!("{}{}{}", self.prefix, uuid::Uuid::new_v4(), suffix),
+            // The extension is kept so it can be found again once the file is renamed
+            #[cfg(feature = "sha2")]
+            Naming::ContentHash => format!(".{}.part{}", self.unique(), suffix),
+        };
+
+        Some(self.directory().join(name))
+    }
+
+    #[cfg(feature = "sha2")]
+    fn hashes_content(&self) -> bool {
+        self.naming == Naming::ContentHash
+    }
+
+    #[cfg(feature = "sha2")]
+    fn hashed_filename(&self, stored_as: &Path, digest: &[u8]) -> Option<PathBuf> {
+        let temporary = stored_as.file_name()?.to_str()?;
+        let suffix = &temporary[temporary.find(".part")? + ".part".len()..];
+
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+        Some(stored_as.with_file_name(format!("{}{}{}", self.prefix, hex, suffix)))
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{ops, path::PathBuf};
+
+use bytes::Bytes;
+
+use crate::types::{Value, ValueMap};
+
+/// A type that can be used to look up a value inside a `Value`
+///
+/// Strings look up keys in a `Value::Map`, and integers look up positions in a `Value::Array`.
+pub trait ValueIndex {
+    fn index_into<'a>(&self, value: &'a Value) -> Option<&'a Value>;
+
+    fn index_into_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value>;
+
+    fn remove_from(&self, value: &mut Value) -> Option<Value>;
+}
+
+impl ValueIndex for str {
+    fn index_into<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        value.as_map().and_then(|map| map.get(self))
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        match *value {
+            Value::Map(ref mut map) => map.get_mut(self),
+            _ => None,
+        }
+    }
+
+    fn remove_from(&self, value: &mut Value) -> Option<Value> {
+        match *value {
+            Value::Map(ref mut map) => map.shift_remove(self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for String {
+    fn index_into<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.as_str().index_into(value)
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        self.as_str().index_into_mut(value)
+    }
+
+    fn remove_from(&self, value: &mut Value) -> Option<Value> {
+        self.as_str().remove_from(value)
+    }
+}
+
+impl ValueIndex for usize {
+    fn index_into<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        value.as_array().and_then(|vec| vec.get(*self))
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        match *value {
+            Value::Array(ref mut vec) => vec.get_mut(*self),
+            _ => None,
+        }
+    }
+
+    fn remove_from(&self, value: &mut Value) -> Option<Value> {
+        match *value {
+            Value::Array(ref mut vec) if *self < vec.len() => Some(vec.remove(*self)),
+            _ => None,
+        }
+    }
+}
+
+impl<T> ValueIndex for &T
+where
+    T: ValueIndex + ?Sized,
+{
+    fn index_into<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        (**self).index_into_mut(value)
+    }
+
+    fn remove_from(&self, value: &mut Value) -> Option<Value> {
+        (**self).remove_from(value)
+    }
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+impl Value {
+    /// Get a reference to a value in a map or array
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Value, ValueMap};
+    /// let mut hm = ValueMap::new();
+    /// hm.insert("Hey".to_owned(), Value::Text("hi".to_owned()));
+    /// let value = Value::Map(hm);
+    ///
+    /// assert_eq!(value.get("Hey").and_then(Value::as_text), Some("hi"));
+    /// ```
+    pub fn get<I>(&self, index: I) -> Option<&Value>
+    where
+        I: ValueIndex,
+    {
+        index.index_into(self)
+    }
+
+    /// Get a mutable reference to a value in a map or array
+    pub fn get_mut<I>(&mut self, index: I) -> Option<&mut Value>
+    where
+        I: ValueIndex,
+    {
+        index.index_into_mut(self)
+    }
+
+    /// Follow a sequence of keys and positions into nested values
+    ///
+    /// Positions must be `usize`s.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Value;
+    /// # let value = Value::Array(vec![]);
+    /// let first_file = value.get_path(&[&"files", &0usize]);
+    /// ```
+    pub fn get_path(&self, path: &[&ValueIndex]) -> Option<&Value> {
+        path.iter()
+            .try_fold(self, |value, index| index.index_into(value))
+    }
+
+    /// Look up a nested value with a slash-separated path, such as `Hi/One` or `files/0`
+    ///
+    /// The path is read like a JSON Pointer, so a leading slash is allowed, and `~1` and `~0`
+    /// stand for `/` and `~` within keys. Segments are treated as keys in maps, and as
+    /// positions in arrays.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Value, ValueMap};
+    /// let mut hi = ValueMap::new();
+    /// hi.insert("One".to_owned(), Value::Int(1));
+    ///
+    /// let mut hm = ValueMap::new();
+    /// hm.insert("Hi".to_owned(), Value::Map(hi));
+    /// let value = Value::Map(hm);
+    ///
+    /// assert_eq!(value.pointer("Hi/One").and_then(Value::as_int), Some(1));
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        let pointer = pointer.trim_start_matches('/');
+
+        if pointer.is_empty() {
+            return Some(self);
+        }
+
+        pointer
+            .split('/')
+            .try_fold(self, |value, token| match *value {
+                Value::Map(ref map) => map.get(&unescape(token)),
+                Value::Array(ref vec) => token.parse::<usize>().ok().and_then(|i| vec.get(i)),
+                _ => None,
+            })
+    }
+
+    /// Look up a nested value with a slash-separated path, returning a mutable reference
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        let pointer = pointer.trim_start_matches('/');
+
+        if pointer.is_empty() {
+            return Some(self);
+        }
+
+        pointer
+            .split('/')
+            .try_fold(self, |value, token| match value {
+                Value::Map(map) => map.get_mut(&unescape(token)),
+                Value::Array(vec) => token
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(move |i| vec.get_mut(i)),
+                _ => None,
+            })
+    }
+
+    /// Remove a value from a map or array, returning it
+    ///
+    /// Removing from an array shifts the following elements down.
+    pub fn take<I>(&mut self, index: I) -> Option<Value>
+    where
+        I: ValueIndex,
+    {
+        index.remove_from(self)
+    }
+
+    /// Remove a map from a map or array, leaving other types of values in place
+    pub fn take_map<I>(&mut self, index: I) -> Option<ValueMap>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_map().is_some())
+            .and_then(Value::map)
+    }
+
+    /// Remove an array from a map or array, leaving other types of values in place
+    pub fn take_array<I>(&mut self, index: I) -> Option<Vec<Value>>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_array().is_some())
+            .and_then(Value::array)
+    }
+
+    /// Remove a file from a map or array, leaving other types of values in place
+    pub fn take_file<I>(&mut self, index: I) -> Option<(String, PathBuf)>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_file().is_some())
+            .and_then(Value::file)
+    }
+
+    /// Remove text from a map or array, leaving other types of values in place
+    pub fn take_text<I>(&mut self, index: I) -> Option<String>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_text().is_some())
+            .and_then(Value::text)
+    }
+
+    /// Remove an int from a map or array, leaving other types of values in place
+    pub fn take_int<I>(&mut self, index: I) -> Option<i64>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_int().is_some())
+            .and_then(Value::int)
+    }
+
+    /// Remove a float from a map or array, leaving other types of values in place
+    pub fn take_float<I>(&mut self, index: I) -> Option<f64>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_float().is_some())
+            .and_then(Value::float)
+    }
+
+    /// Remove a bool from a map or array, leaving other types of values in place
+    pub fn take_bool<I>(&mut self, index: I) -> Option<bool>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_bool().is_some())
+            .and_then(Value::bool)
+    }
+
+    /// Remove bytes from a map or array, leaving other types of values in place
+    pub fn take_bytes<I>(&mut self, index: I) -> Option<Bytes>
+    where
+        I: ValueIndex,
+    {
+        self.take_if(index, |v| v.as_bytes().is_some())
+            .and_then(Value::bytes)
+    }
+
+    fn take_if<I, F>(&mut self, index: I, check: F) -> Option<Value>
+    where
+        I: ValueIndex,
+        F: Fn(&Value) -> bool,
+    {
+        if index.index_into(self).map(check).unwrap_or(false) {
+            index.remove_from(self)
+        } else {
+            None
+        }
+    }
+}
+
+impl<I> ops::Index<I> for Value
+where
+    I: ValueIndex,
+{
+    type Output = Value;
+
+    /// Get a reference to a value in a map or array
+    ///
+    /// # Panics
+    ///
+    /// Panics if this isn't a map or array, or if the key or position isn't present. Use `get`
+    /// to handle missing values.
+    fn index(&self, index: I) -> &Value {
+        index
+            .index_into(self)
+            .expect("No value at the given key or position")
+    }
+}
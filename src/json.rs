@@ -0,0 +1,327 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::collections::{HashSet, VecDeque};
+
+use actix_web::error::PayloadError;
+use bytes::Bytes;
+use futures::{Future, Stream};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    error::Error,
+    types::{Field, FieldTerminator, Form, Map, MultipartContent, UnknownFields, Value, ValueMap},
+    upload::{complete, read_body},
+};
+
+/// A definition of a field containing a JSON document
+///
+/// By default, the document is converted into nested `Value::Map`, `Value::Array`, and scalar
+/// values. JSON `null`s are treated as absent and left out of the result. Use `raw` to keep the
+/// document as a `serde_json::Value` instead, or `schema` to validate it against a `Form`.
+#[derive(Debug, Clone)]
+pub struct Json {
+    raw: bool,
+    schema: Option<Map>,
+}
+
+impl Json {
+    pub(crate) fn new() -> Self {
+        Json {
+            raw: false,
+            schema: None,
+        }
+    }
+
+    /// Keep the parsed document as a `Value::Json`
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::json().raw();
+    /// ```
+    pub fn raw(mut self) -> Self {
+        self.raw = true;
+
+        self
+    }
+
+    /// Validate the document against the fields of the provided `Form`
+    ///
+    /// Keys that aren't defined by the form are handled by its `unknown_fields` policy, and
+    /// values are converted to the types of their matching fields, just as they would be if they
+    /// were submitted as form data. Bytes fields are expected as base64 strings. The limits of
+    /// the provided form are not used. File fields cannot be provided through JSON.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, Form};
+    /// #
+    /// Field::json().schema(
+    ///     Form::new()
+    ///         .field("title", Field::text())
+    ///         .field("tags", Field::array(Field::text())),
+    /// );
+    /// ```
+    pub fn schema(mut self, form: Form) -> Self {
+        self.schema = Some(form.fields().clone());
+
+        self
+    }
+
+    /// Finalize the JSON definition into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Json(self)
+    }
+
+    pub(crate) fn parse(&self, name: &str, bytes: &[u8]) -> Result<MultipartContent, Error> {
+        let json = serde_json::from_slice(bytes).map_err(Error::ParseJson)?;
+
+        self.convert(name, json).map(MultipartContent::Json)
+    }
+
+    fn convert(&self, name: &str, json: JsonValue) -> Result<Value, Error> {
+        if self.raw {
+            return Ok(Value::Json(json));
+        }
+
+        match self.schema {
+            Some(ref map) => {
+                let mut value = validate_map(map, name, json)?;
+
+                if let Value::Map(ref mut hm) = value {
+                    map.fill_defaults(hm);
+                }
+
+                Ok(value)
+            }
+            None => to_value(json).ok_or(Error::FieldType),
+        }
+    }
+}
+
+impl From<Json> for Field {
+    fn from(json: Json) -> Self {
+        json.finalize()
+    }
+}
+
+fn to_value(json: JsonValue) -> Option<Value> {
+    match json {
+        JsonValue::Null => None,
+        JsonValue::Bool(b) => Some(Value::Bool(b)),
+        JsonValue::Number(n) => n
+            .as_i64()
+            .map(Value::Int)
+            .or_else(|| n.as_f64().map(Value::Float)),
+        JsonValue::String(s) => Some(Value::Text(s)),
+        JsonValue::Array(vec) => Some(Value::Array(vec.into_iter().filter_map(to_value).collect())),
+        JsonValue::Object(obj) => Some(Value::Map(
+            obj.into_iter()
+                .filter_map(|(key, value)| to_value(value).map(|value| (key, value)))
+                .collect(),
+        )),
+    }
+}
+
+fn child_name(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}[{}]", parent, key)
+    }
+}
+
+pub(crate) fn validate_map(map: &Map, name: &str, json: JsonValue) -> Result<Value, Error> {
+    let obj = match json {
+        JsonValue::Object(obj) => obj,
+        _ => return Err(Error::FieldType),
+    };
+
+    let mut hm = ValueMap::new();
+
+    let mut collected = ValueMap::new();
+
+    for (key, json) in obj {
+        let field = match map.get(&key) {
+            Some(field) => field,
+            None => match *map.unknown() {
+                UnknownFields::Reject => return Err(Error::FieldType),
+                UnknownFields::Skip => continue,
+                UnknownFields::Collect(_) => {
+                    if let Some(value) = to_value(json) {
+                        collected.insert(key, value);
+                    }
+                    continue;
+                }
+            },
+        };
+
+        if let Some(value) = validate_field(field, &child_name(name, &key), json)? {
+            hm.insert(key, value);
+        }
+    }
+
+    if let UnknownFields::Collect(ref key) = *map.unknown() {
+        if !collected.is_empty() {
+            hm.insert(key.to_owned(), Value::Map(collected));
+        }
+    }
+
+    Ok(Value::Map(hm))
+}
+
+fn validate_field(field: &Field, name: &str, json: JsonValue) -> Result<Option<Value>, Error> {
+    if json.is_null() {
+        return Ok(None);
+    }
+
+    match *field {
+        Field::Map(ref map) => validate_map(map, name, json).map(Some),
+        Field::Dict(ref dict) => match json {
+            JsonValue::Object(obj) => {
+                dict.check_len(obj.len())?;
+
+                let mut hm = ValueMap::new();
+
+                for (key, json) in obj {
+                    if !dict.valid_key(&key) {
+                        return Err(Error::FieldType);
+                    }
+
+                    if let Some(value) =
+                        validate_field(dict.field(), &child_name(name, &key), json)?
+                    {
+                        hm.insert(key, value);
+                    }
+                }
+
+                Ok(Some(Value::Map(hm)))
+            }
+            _ => Err(Error::FieldType),
+        },
+        Field::Array(ref arr) => match json {
+            JsonValue::Array(vec) => {
+                let name = format!("{}[]", name);
+                let mut values = Vec::new();
+
+                for json in vec {
+                    if let Some(value) = validate_field(arr.field(), &name, json)? {
+                        values.push(value);
+                    }
+                }
+
+                Ok(Some(Value::Array(values)))
+            }
+            _ => Err(Error::FieldType),
+        },
+        Field::Json(ref inner) => inner.convert(name, json).map(Some),
+        ref field => {
+            let term = field
+                .valid_field(VecDeque::new(), &mut Vec::new())
+                .ok_or(Error::FieldType)?;
+
+            validate_scalar(&term, name, json).map(|content| Some(Value::from(content)))
+        }
+    }
+}
+
+fn validate_scalar(
+    term: &FieldTerminator,
+    name: &str,
+    json: JsonValue,
+) -> Result<MultipartContent, Error> {
+    match (term, json) {
+        (FieldTerminator::Bool(_), JsonValue::Bool(b)) => Ok(MultipartContent::Bool(b)),
+        (FieldTerminator::Bytes, JsonValue::String(s)) => base64::decode(&s)
+            .map(|bytes| MultipartContent::Bytes(bytes.into()))
+            .map_err(Error::ParseBase64),
+        (term, JsonValue::String(s)) => term.parse_text(name, s),
+        (term, JsonValue::Number(n)) => term.parse_text(name, n.to_string()),
+        (term, JsonValue::Bool(b)) => term.parse_text(name, b.to_string()),
+        _ => Err(Error::FieldType),
+    }
+}
+
+fn check_limits(form: &Form, value: &Value, count: &mut u32) -> Result<(), Error> {
+    match *value {
+        Value::Map(ref hm) => {
+            for value in hm.values() {
+                check_limits(form, value, count)?;
+            }
+
+            return Ok(());
+        }
+        Value::Array(ref vec) => {
+            for value in vec {
+                check_limits(form, value, count)?;
+            }
+
+            return Ok(());
+        }
+        Value::Text(ref text) if text.len() >= form.max_field_size => return Err(Error::FieldSize),
+        Value::Bytes(ref bytes) if bytes.len() >= form.max_field_size => {
+            return Err(Error::FieldSize)
+        }
+        _ => (),
+    }
+
+    *count += 1;
+
+    if *count >= form.max_fields {
+        Err(Error::FieldCount)
+    } else {
+        Ok(())
+    }
+}
+
+/// Handle JSON request bodies with the same `Form` used for multipart
+///
+/// The document must be an object, and is walked against the form's fields with the same
+/// conversions as form data, so one schema can serve both kinds of request. Bytes fields are
+/// expected as base64 strings, and file fields can't be submitted this way. The form's field
+/// count and size limits apply to each scalar value, and bodies larger than `max_fields` times
+/// `max_field_size` are rejected before parsing.
+///
+/// # Example
+/// ```rust
+/// # use actix_web::web::Payload;
+/// # use form_data::{handle_json, Error, Form, Value};
+/// # use futures::Future;
+/// fn submit(body: Payload, form: Form) -> Box<Future<Item = Value, Error = Error>> {
+///     handle_json(body, form)
+/// }
+/// ```
+pub fn handle_json<S>(body: S, form: Form) -> Box<Future<Item = Value, Error = Error>>
+where
+    S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    Box::new(
+        read_body(body, form.max_body_size())
+            .and_then(move |bytes| {
+                let json = serde_json::from_slice(&bytes).map_err(Error::ParseJson)?;
+                let value = validate_map(form.fields(), "", json)?;
+                check_limits(&form, &value, &mut 0)?;
+
+                Ok((form, value))
+            })
+            .and_then(|(form, value)| complete(form, value, HashSet::new())),
+    )
+}
@@ -32,13 +32,13 @@
 //!     web::{post, resource, Data},
 //!     App, HttpResponse, HttpServer,
 //! };
-//! use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form};
+//! use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form, UploadContext};
 //! use futures::Future;
 //! 
 //! struct Gen;
 //! 
 //! impl FilenameGenerator for Gen {
-//!     fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
+//!     fn next_filename(&self, _: &UploadContext) -> Option<PathBuf> {
 //!         let mut p = PathBuf::new();
 //!         p.push("examples/filename.png");
 //!         Some(p)
@@ -80,19 +80,199 @@
 //! }
 //!```
 
-use std::path::PathBuf;
+use std::{
+    any::Any,
+    fmt,
+    path::{Path, PathBuf},
+};
 
+use futures::{future::result, Future};
+
+mod charset;
+mod context;
+mod custom;
+#[cfg(feature = "chrono")]
+mod datetime;
+#[cfg(feature = "serde")]
+mod de;
+mod disposition;
 mod error;
 mod file_future;
+mod generators;
+mod index;
+#[cfg(feature = "serde_json")]
+mod json;
+mod name;
+mod sanitize;
+#[cfg(feature = "serde")]
+mod ser;
+mod storage;
+mod transfer;
 mod types;
 mod upload;
+mod urlencoded;
 
-pub use self::{error::Error, types::*, upload::handle_multipart};
+#[cfg(feature = "chrono")]
+pub use self::datetime::{Date, DateTime, Time, Timestamp};
+#[cfg(feature = "serde")]
+pub use self::de::from_value;
+#[cfg(feature = "serde_json")]
+pub use self::json::{handle_json, Json};
+pub use self::{
+    context::UploadContext,
+    custom::{Custom, CustomValue},
+    error::{Constraint, Error},
+    generators::{mime_extension, Extension, Generator, Naming},
+    index::ValueIndex,
+    name::{Brackets, Dotted, Repeated},
+    sanitize::{AllowedChars, Sanitizer},
+    types::*,
+    upload::{handle_multipart, handle_multipart_request},
+    urlencoded::handle_urlencoded,
+};
 
 /// A trait for types that produce filenames for uploade files
 ///
-/// Currently, the mime type provided to the `next_filename` method is guessed from the uploaded
-/// file's original filename, so relying on this to be 100% accurate is probably a bad idea.
+/// The `UploadContext` describes the file being uploaded, including the field it was sent for,
+/// its sanitized filename, and the `Content-Type` the client declared for it. That content type
+/// is whatever the client says it is, so relying on it to be 100% accurate is probably a bad
+/// idea.
+///
+/// `Generator` covers the common cases, like UUID names, date-sharded directories, and names
+/// taken from a file's contents. Generators that need to look things up elsewhere can
+/// implement `AsyncFilenameGenerator` instead.
 pub trait FilenameGenerator: Send + Sync {
-    fn next_filename(&self, mime_type: &mime::Mime) -> Option<PathBuf>;
+    fn next_filename(&self, ctx: &UploadContext) -> Option<PathBuf>;
+
+    /// Whether files should be moved to `hashed_filename` once they're written
+    ///
+    /// Files are only hashed when the `sha2` feature is enabled, otherwise this is ignored.
+    fn hashes_content(&self) -> bool {
+        false
+    }
+
+    /// Pick the final path of a file from the SHA-256 digest of its contents
+    ///
+    /// The new path must be in the same directory as the one the file was written to.
+    fn hashed_filename(&self, _stored_as: &Path, _digest: &[u8]) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// A trait for types that produce filenames for uploaded files asynchronously
+///
+/// This is useful for generators that consult external state, like a database, to pick a
+/// name. Anything needed from the `UploadContext` has to be copied out of it before the future
+/// is returned. Every `FilenameGenerator` is also an `AsyncFilenameGenerator`, so either can be
+/// passed to `Field::file`.
+///
+/// # Example
+/// ```rust
+/// # use std::path::PathBuf;
+/// # use form_data::{AsyncFilenameGenerator, Error, Field, Form, UploadContext};
+/// # use futures::{future::result, Future};
+/// struct PerUser;
+///
+/// impl AsyncFilenameGenerator for PerUser {
+///     fn generate(&self, ctx: &UploadContext) -> Box<Future<Item = PathBuf, Error = Error>> {
+///         let user = ctx
+///             .request()
+///             .and_then(|req| req.headers().get("x-user-id"))
+///             .and_then(|id| id.to_str().ok())
+///             .filter(|id| id.bytes().all(|b| b.is_ascii_digit()))
+///             .map(|id| id.to_owned());
+///         let filename = ctx.filename().to_owned();
+///
+///         Box::new(result(
+///             user.map(|user| PathBuf::from("uploads").join(user).join(filename))
+///                 .ok_or(Error::GenFilename),
+///         ))
+///     }
+/// }
+///
+/// let form = Form::new().field("file", Field::file(PerUser));
+/// ```
+pub trait AsyncFilenameGenerator: Send + Sync {
+    fn generate(&self, ctx: &UploadContext) -> Box<Future<Item = PathBuf, Error = Error>>;
+
+    /// Whether files should be moved to `hashed_filename` once they're written
+    ///
+    /// Files are only hashed when the `sha2` feature is enabled, otherwise this is ignored.
+    fn hashes_content(&self) -> bool {
+        false
+    }
+
+    /// Pick the final path of a file from the SHA-256 digest of its contents
+    ///
+    /// The new path must be in the same directory as the one the file was written to.
+    fn hashed_filename(&self, _stored_as: &Path, _digest: &[u8]) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl<T> AsyncFilenameGenerator for T
+where
+    T: FilenameGenerator,
+{
+    fn generate(&self, ctx: &UploadContext) -> Box<Future<Item = PathBuf, Error = Error>> {
+        Box::new(result(self.next_filename(ctx).ok_or(Error::GenFilename)))
+    }
+
+    fn hashes_content(&self) -> bool {
+        FilenameGenerator::hashes_content(self)
+    }
+
+    fn hashed_filename(&self, stored_as: &Path, digest: &[u8]) -> Option<PathBuf> {
+        FilenameGenerator::hashed_filename(self, stored_as, digest)
+    }
+}
+
+/// A trait for types that split a field's name into the parts used to find it in a `Form`
+///
+/// Built-in parsers are provided for PHP-style names (`Brackets`, the default), dotted names
+/// (`Dotted`), and for treating repeated names as arrays (`Repeated`).
+pub trait NameParser: Send + Sync {
+    /// Split a name into its parts. The first part must be a `NamePart::Map`.
+    fn parse(&self, name: &str) -> Result<Vec<NamePart>, Error>;
+
+    /// Whether a name that stops at an array field refers to a new element of that array
+    fn implicit_arrays(&self) -> bool {
+        false
+    }
+}
+
+/// A trait for types that parse the contents of a field into a custom value
+///
+/// The field is buffered in memory (subject to the form's `max_field_size`) before being handed
+/// to the parser. Successfully parsed values end up in the resulting `Value` as a
+/// `Value::Custom`, and errors are reported as `Error::Custom` along with the name of the field.
+///
+/// # Example
+/// ```rust
+/// # use form_data::{Field, FieldParser, Form};
+/// #[derive(Debug)]
+/// struct Coordinates(f64, f64);
+///
+/// struct CoordinatesParser;
+///
+/// impl FieldParser for CoordinatesParser {
+///     type Output = Coordinates;
+///
+///     fn parse(&self, bytes: &[u8]) -> Result<Coordinates, failure::Error> {
+///         let s = std::str::from_utf8(bytes)?;
+///         let mut parts = s.splitn(2, ',');
+///
+///         match (parts.next(), parts.next()) {
+///             (Some(lat), Some(lng)) => Ok(Coordinates(lat.trim().parse()?, lng.trim().parse()?)),
+///             _ => Err(failure::err_msg("Expected 'lat,lng'")),
+///         }
+///     }
+/// }
+///
+/// let form = Form::new().field("location", Field::custom(CoordinatesParser));
+/// ```
+pub trait FieldParser: Send + Sync {
+    type Output: Any + fmt::Debug + Send + Sync;
+
+    fn parse(&self, bytes: &[u8]) -> Result<Self::Output, failure::Error>;
 }
@@ -0,0 +1,223 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::{error::Error, types::NamePart, NameParser};
+
+fn bracket_part(part: &str) -> NamePart {
+    if part.is_empty() {
+        return NamePart::Array;
+    }
+
+    // Only canonical numbers are positions, so keys like `007` keep their leading zeros
+    if part.bytes().all(|b| b.is_ascii_digit()) && (part == "0" || !part.starts_with('0')) {
+        if let Ok(index) = part.parse() {
+            return NamePart::Index(index);
+        }
+    }
+
+    NamePart::Map(part.to_owned())
+}
+
+/// Push a segment like `name`, `name[]`, or `name[0][key]` onto `parts`
+fn push_segment(segment: &str, parts: &mut Vec<NamePart>) -> Result<(), Error> {
+    let mut split = segment.split('[');
+
+    match split.next() {
+        Some(key) if !key.is_empty() && !key.contains(']') => {
+            parts.push(NamePart::Map(key.to_owned()))
+        }
+        _ => return Err(Error::ContentDisposition),
+    }
+
+    for part in split {
+        if !part.ends_with(']') {
+            return Err(Error::ContentDisposition);
+        }
+
+        parts.push(bracket_part(&part[..part.len() - 1]));
+    }
+
+    Ok(())
+}
+
+/// PHP-style names, such as `user[name]`, `tags[]`, and `items[0][qty]`
+///
+/// This is the default for a `Form`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Brackets;
+
+impl NameParser for Brackets {
+    fn parse(&self, name: &str) -> Result<Vec<NamePart>, Error> {
+        let v: Vec<NamePart> = name
+            .split('[')
+            .map(|part| {
+                if part.ends_with(']') {
+                    bracket_part(part.trim_end_matches(']'))
+                } else {
+                    NamePart::Map(part.to_owned())
+                }
+            })
+            .collect();
+
+        match v.first() {
+            Some(part) if part.is_map() => Ok(v),
+            _ => Err(Error::ContentDisposition),
+        }
+    }
+}
+
+/// Dotted names, as sent by Spring and many Go form libraries, such as `user.name`, `tags[]`,
+/// and `items[0].qty`
+///
+/// Bracketed segments are understood the same way as with `Brackets`, so `user[name]` still
+/// works.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Dotted;
+
+impl NameParser for Dotted {
+    fn parse(&self, name: &str) -> Result<Vec<NamePart>, Error> {
+        let mut v = Vec::new();
+
+        for segment in name.split('.') {
+            push_segment(segment, &mut v)?;
+        }
+
+        Ok(v)
+    }
+}
+
+/// Treat names that stop at an array field as a new element of that array
+///
+/// This wraps another `NameParser`, so repeated bare names like `tag=a&tag=b` build an array
+/// without the client adding `[]` to each name.
+///
+/// # Example
+/// ```rust
+/// # use form_data::{Dotted, Field, Form, Repeated};
+/// #
+/// let form = Form::new()
+///     .field("tag", Field::array(Field::text()))
+///     .name_parser(Repeated::new(Dotted));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Repeated<P> {
+    inner: P,
+}
+
+impl<P> Repeated<P>
+where
+    P: NameParser,
+{
+    /// Wrap the provided `NameParser`
+    pub fn new(inner: P) -> Self {
+        Repeated { inner }
+    }
+}
+
+impl<P> NameParser for Repeated<P>
+where
+    P: NameParser,
+{
+    fn parse(&self, name: &str) -> Result<Vec<NamePart>, Error> {
+        self.inner.parse(name)
+    }
+
+    fn implicit_arrays(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NamePart::{Array, Index, Map};
+
+    fn map(key: &str) -> NamePart {
+        Map(key.to_owned())
+    }
+
+    #[test]
+    fn brackets_indices() {
+        let cases = vec![
+            ("items[0]", vec![map("items"), Index(0)]),
+            ("items[12][qty]", vec![map("items"), Index(12), map("qty")]),
+            ("items[0][1]", vec![map("items"), Index(0), Index(1)]),
+            ("items[-1]", vec![map("items"), map("-1")]),
+            ("items[1a]", vec![map("items"), map("1a")]),
+            ("items[007]", vec![map("items"), map("007")]),
+            ("zip[02134]", vec![map("zip"), map("02134")]),
+            ("zip[2134]", vec![map("zip"), Index(2134)]),
+            (
+                "items[99999999999999999999999]",
+                vec![map("items"), map("99999999999999999999999")],
+            ),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(Brackets.parse(name).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn brackets() {
+        let cases = vec![
+            ("name", vec![map("name")]),
+            ("user[name]", vec![map("user"), map("name")]),
+            ("tags[]", vec![map("tags"), Array]),
+            ("user.name", vec![map("user.name")]),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(Brackets.parse(name).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn dotted() {
+        let cases = vec![
+            ("name", vec![map("name")]),
+            ("user.name", vec![map("user"), map("name")]),
+            ("tags[]", vec![map("tags"), Array]),
+            ("items[0].qty", vec![map("items"), Index(0), map("qty")]),
+            ("user[name]", vec![map("user"), map("name")]),
+            ("a.b[]", vec![map("a"), map("b"), Array]),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(Dotted.parse(name).unwrap(), expected, "{}", name);
+        }
+
+        for name in &["", ".name", "name.", "a..b", "[0]", "a[0", "a]b", "a[0]b"] {
+            assert!(Dotted.parse(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn repeated() {
+        let parser = Repeated::new(Dotted);
+
+        assert_eq!(
+            parser.parse("user.name").unwrap(),
+            vec![map("user"), map("name")]
+        );
+        assert!(parser.implicit_arrays());
+        assert!(!Dotted.implicit_arrays());
+        assert!(!Brackets.implicit_arrays());
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "unicode-normalization")]
+use unicode_normalization::UnicodeNormalization;
+
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows doesn't allow in filenames
+const FORBIDDEN: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Invisible characters that change the direction text is displayed in, which can disguise a
+/// name like `photo\u{202E}gpj.exe` as `photoexe.jpg`
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Characters that are removed or replaced no matter which characters are allowed
+fn is_stripped(c: char) -> bool {
+    c == '/' || c == '\\' || c.is_control() || is_bidi_control(c) || FORBIDDEN.contains(&c)
+}
+
+/// Whether the part of a name before its first dot is a name Windows reserves
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+
+    RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// Cut `s` down to at most `max` bytes without splitting a character
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = max.min(s.len());
+
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+fn trim(s: &str) -> &str {
+    s.trim_matches(|c: char| c == '.' || c.is_whitespace())
+}
+
+/// The characters a `Sanitizer` keeps in filenames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AllowedChars {
+    /// Any printable character
+    Any,
+    /// Printable ASCII characters
+    Ascii,
+    /// The POSIX portable filename characters, `A-Z`, `a-z`, `0-9`, `.`, `-`, and `_`
+    Portable,
+}
+
+impl AllowedChars {
+    fn allows(&self, c: char) -> bool {
+        match *self {
+            AllowedChars::Any => true,
+            AllowedChars::Ascii => c.is_ascii(),
+            AllowedChars::Portable => c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_',
+        }
+    }
+}
+
+/// Cleans up the filenames clients submit
+///
+/// A `Form` runs the original filename of each upload through its sanitizer before it's
+/// reported in `Value::File`. By default, this
+///  - keeps only the last component of the path, whether it's separated by `/` or `\`
+///  - removes control characters and the invisible characters that reorder text
+///  - replaces the characters Windows doesn't allow with `_`
+///  - removes leading and trailing dots and whitespace
+///  - prefixes names Windows reserves for devices, like `CON` and `lpt1.txt`, with `_`
+///  - limits the name to 255 bytes, keeping the extension
+///  - normalizes the name to Unicode NFC when the `unicode-normalization` feature is enabled
+///
+/// Names that end up empty are rejected. A `FilenameGenerator` can use the same rules to
+/// build names of its own.
+///
+/// # Example
+/// ```rust
+/// # use form_data::{AllowedChars, Sanitizer};
+/// #
+/// let sanitizer = Sanitizer::new()
+///     .max_len(64)
+///     .allowed_chars(AllowedChars::Portable);
+///
+/// assert_eq!(
+///     sanitizer.sanitize("C:\\Users\\me\\my photo.png"),
+///     Some("my_photo.png".to_owned())
+/// );
+/// assert_eq!(sanitizer.sanitize("../.."), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sanitizer {
+    max_len: usize,
+    allowed: AllowedChars,
+    preserve_extension: bool,
+    replacement: char,
+    #[cfg(feature = "unicode-normalization")]
+    normalize: bool,
+}
+
+impl Sanitizer {
+    /// Create a sanitizer with the default rules
+    pub fn new() -> Self {
+        Sanitizer {
+            max_len: 255,
+            allowed: AllowedChars::Any,
+            preserve_extension: true,
+            replacement: '_',
+            #[cfg(feature = "unicode-normalization")]
+            normalize: true,
+        }
+    }
+
+    /// Set the maximum length of a filename, in bytes
+    pub fn max_len(mut self, max: usize) -> Self {
+        self.max_len = max;
+
+        self
+    }
+
+    /// Set which characters are kept, others are replaced
+    pub fn allowed_chars(mut self, allowed: AllowedChars) -> Self {
+        self.allowed = allowed;
+
+        self
+    }
+
+    /// Set whether the extension is kept when a long name is shortened
+    pub fn preserve_extension(mut self, preserve: bool) -> Self {
+        self.preserve_extension = preserve;
+
+        self
+    }
+
+    /// Set the character used in place of characters that aren't allowed
+    ///
+    /// The replacement can't be a character the sanitizer would remove or replace itself, so
+    /// path separators, control characters, the characters Windows doesn't allow, and the
+    /// invisible characters that reorder text are ignored, keeping the current replacement. A
+    /// replacement that isn't one of the allowed characters falls back to `_` when filenames are
+    /// sanitized.
+    pub fn replacement(mut self, replacement: char) -> Self {
+        if !is_stripped(replacement) {
+            self.replacement = replacement;
+        }
+
+        self
+    }
+
+    /// Set whether filenames are normalized to Unicode NFC
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+
+        self
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    fn normalized(&self, name: &str) -> String {
+        if self.normalize {
+            name.nfc().collect()
+        } else {
+            name.to_owned()
+        }
+    }
+
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn normalized(&self, name: &str) -> String {
+        name.to_owned()
+    }
+
+    fn shorten(&self, name: &str) -> String {
+        if name.len() <= self.max_len {
+            return name.to_owned();
+        }
+
+        if self.preserve_extension {
+            if let Some(pos) = name.rfind('.').filter(|&pos| pos > 0) {
+                let (stem, extension) = name.split_at(pos);
+
+                if extension.len() < self.max_len {
+                    let stem = truncate(stem, self.max_len - extension.len());
+
+                    return format!("{}{}", trim(stem), extension);
+                }
+            }
+        }
+
+        truncate(name, self.max_len).to_owned()
+    }
+
+    /// Clean up a filename, returning `None` if nothing usable is left
+    pub fn sanitize(&self, filename: &str) -> Option<String> {
+        let name = filename.rsplit(['/', '\\']).next()?;
+        let name = self.normalized(name);
+
+        let replacement = if self.allowed.allows(self.replacement) {
+            self.replacement
+        } else {
+            '_'
+        };
+
+        let name: String = name
+            .chars()
+            .filter(|&c| !c.is_control() && !is_bidi_control(c))
+            .map(|c| {
+                if FORBIDDEN.contains(&c) || !self.allowed.allows(c) {
+                    replacement
+                } else {
+                    c
+                }
+            })
+            .collect();
+
+        let name = self.shorten(trim(&name));
+        let name = trim(&name);
+
+        if name.is_empty() {
+            return None;
+        }
+
+        // Shortening can turn a name like `CONSOLE.txt` into a reserved one, so this is checked
+        // afterwards, and the prefixed name is shortened again to keep its extension
+        if is_reserved(name) {
+            let name = self.shorten(&format!("_{}", name));
+
+            return Some(trim(&name).to_owned());
+        }
+
+        Some(name.to_owned())
+    }
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Sanitizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AllowedChars, Sanitizer};
+
+    #[test]
+    fn sanitize() {
+        let sanitizer = Sanitizer::new();
+
+        let cases = vec![
+            ("photo.png", Some("photo.png")),
+            ("../../etc/passwd", Some("passwd")),
+            ("C:\\Users\\me\\photo.png", Some("photo.png")),
+            ("a<b>c.txt", Some("a_b_c.txt")),
+            ("photo\u{202E}gnp.exe", Some("photognp.exe")),
+            ("  .hidden. ", Some("hidden")),
+            ("CON", Some("_CON")),
+            ("lpt1.txt", Some("_lpt1.txt")),
+            ("console.txt", Some("console.txt")),
+            ("..", None),
+            ("dir/", None),
+        ];
+
+        for (filename, expected) in cases {
+            assert_eq!(
+                sanitizer.sanitize(filename).as_deref(),
+                expected,
+                "{:?}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn reserved_names_keep_extensions() {
+        let cases = vec![
+            (9, "nul.json", "_nul.json"),
+            (8, "nul.json", "_nu.json"),
+            (7, "console.txt", "_co.txt"),
+        ];
+
+        for (max_len, filename, expected) in cases {
+            let sanitizer = Sanitizer::new().max_len(max_len);
+
+            assert_eq!(
+                sanitizer.sanitize(filename),
+                Some(expected.to_owned()),
+                "{} {:?}",
+                max_len,
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn replacement() {
+        let cases = vec![
+            ('-', "a-b.txt"),
+            ('/', "a_b.txt"),
+            ('\\', "a_b.txt"),
+            ('\0', "a_b.txt"),
+            ('|', "a_b.txt"),
+            ('\u{202E}', "a_b.txt"),
+        ];
+
+        for (replacement, expected) in cases {
+            let sanitizer = Sanitizer::new().replacement(replacement);
+
+            assert_eq!(
+                sanitizer.sanitize("a*b.txt"),
+                Some(expected.to_owned()),
+                "{:?}",
+                replacement
+            );
+        }
+
+        let sanitizer = Sanitizer::new()
+            .replacement('é')
+            .allowed_chars(AllowedChars::Portable);
+
+        assert_eq!(sanitizer.sanitize("a b.txt"), Some("a_b.txt".to_owned()));
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::ser::{Error, Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+use crate::types::Value;
+
+/// Values serialize as they would appear in JSON
+///
+/// Files become objects with `filename`, `path`, and `size` fields, bytes become base64
+/// strings, and dates and times become strings in the same form they deserialize from.
+/// Custom values can't be serialized.
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Value::Map(ref hm) => {
+                let mut map = serializer.serialize_map(Some(hm.len()))?;
+                for (key, value) in hm.iter() {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Array(ref vec) => {
+                let mut seq = serializer.serialize_seq(Some(vec.len()))?;
+                for value in vec {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Value::File {
+                ref filename,
+                ref stored_as,
+                size,
+            } => {
+                let mut file = serializer.serialize_struct("File", 3)?;
+                file.serialize_field("filename", filename)?;
+                file.serialize_field("path", &stored_as.to_string_lossy())?;
+                file.serialize_field("size", &size)?;
+                file.end()
+            }
+            Value::Text(ref text) => serializer.serialize_str(text),
+            Value::Int(int) => serializer.serialize_i64(int),
+            Value::Float(float) => serializer.serialize_f64(float),
+            Value::Bool(b) => serializer.serialize_bool(b),
+            #[cfg(feature = "chrono")]
+            Value::Date(ref date) => serializer.collect_str(date),
+            #[cfg(feature = "chrono")]
+            Value::Time(ref time) => serializer.collect_str(time),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(ref datetime) => serializer.collect_str(datetime),
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(ref timestamp) => serializer.serialize_str(&timestamp.to_rfc3339()),
+            #[cfg(feature = "serde_json")]
+            Value::Json(ref json) => json.serialize(serializer),
+            Value::Custom(_) => Err(S::Error::custom("custom values cannot be serialized")),
+            Value::Bytes(ref bytes) => serializer.serialize_str(&base64::encode(bytes)),
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl Value {
+    /// Convert the value into a `serde_json::Value`
+    ///
+    /// This fails if the value contains a `Value::Custom`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Value, ValueMap};
+    /// let mut hm = ValueMap::new();
+    /// hm.insert("title".to_owned(), Value::Text("Hello".to_owned()));
+    ///
+    /// let json = Value::Map(hm).to_json().unwrap();
+    /// assert_eq!(json["title"], "Hello");
+    /// ```
+    pub fn to_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
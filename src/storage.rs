@@ -0,0 +1,153 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::error::Error;
+
+/// Place a generated path under the storage root
+///
+/// Relative paths are joined onto the root, and absolute paths must already be inside it.
+/// Paths containing `..`, or that don't name anything below the root, are rejected.
+pub(crate) fn resolve(root: &Path, generated: &Path) -> Result<PathBuf, Error> {
+    let relative = if generated.has_root() {
+        generated
+            .strip_prefix(root)
+            .map_err(|_| Error::StorageRoot)?
+    } else {
+        generated
+    };
+
+    let mut resolved = root.to_owned();
+    let mut depth = 0;
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                depth += 1;
+            }
+            Component::CurDir => (),
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(Error::StorageRoot);
+            }
+        }
+    }
+
+    if depth == 0 {
+        return Err(Error::StorageRoot);
+    }
+
+    Ok(resolved)
+}
+
+/// Make sure symlinks on the filesystem don't lead a resolved path out of the storage root
+///
+/// The deepest part of the path that already exists must still be inside the root once links
+/// are followed, and the file itself must not be a link, since creating it would write through
+/// to wherever it points. The root has to exist.
+pub(crate) fn check_links(root: &Path, path: &Path) -> Result<(), Error> {
+    let root = root.canonicalize().map_err(|_| Error::StorageRoot)?;
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_symlink() {
+            return Err(Error::StorageRoot);
+        }
+    }
+
+    let existing = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())
+        .ok_or(Error::StorageRoot)?;
+
+    let existing = existing.canonicalize().map_err(|_| Error::StorageRoot)?;
+
+    if existing.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(Error::StorageRoot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process};
+
+    use super::{check_links, resolve};
+
+    #[test]
+    fn resolved_paths() {
+        let root = PathBuf::from("/srv/uploads");
+
+        let cases = vec![
+            ("a.png", Some("/srv/uploads/a.png")),
+            ("avatars/./a.png", Some("/srv/uploads/avatars/a.png")),
+            (
+                "/srv/uploads/avatars/a.png",
+                Some("/srv/uploads/avatars/a.png"),
+            ),
+            ("../a.png", None),
+            ("avatars/../../a.png", None),
+            ("avatars/../a.png", None),
+            ("/etc/passwd", None),
+            ("/srv/uploads-other/a.png", None),
+            ("/srv/uploads", None),
+            (".", None),
+            ("", None),
+        ];
+
+        for (generated, expected) in cases {
+            assert_eq!(
+                resolve(&root, generated.as_ref()).ok(),
+                expected.map(PathBuf::from),
+                "{:?}",
+                generated
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn linked_directories() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("form-data-storage-{}", process::id()));
+        let root = base.join("root");
+        let outside = base.join("outside");
+
+        fs::create_dir_all(root.join("inside")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        symlink(&outside, root.join("escape")).unwrap();
+        symlink(outside.join("a.png"), root.join("link.png")).unwrap();
+
+        assert!(check_links(&root, &root.join("a.png")).is_ok());
+        assert!(check_links(&root, &root.join("inside/a.png")).is_ok());
+        assert!(check_links(&root, &root.join("new/dir/a.png")).is_ok());
+        assert!(check_links(&root, &root.join("escape/a.png")).is_err());
+        assert!(check_links(&root, &root.join("escape/new/a.png")).is_err());
+        assert!(check_links(&root, &root.join("link.png")).is_err());
+        assert!(check_links(&base.join("missing"), &root.join("a.png")).is_err());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::mem;
+
+use actix_web::http::HeaderMap;
+use bytes::Bytes;
+use futures::{Async, Poll, Stream};
+
+use crate::error::Error;
+
+enum Decoder {
+    Base64(Vec<u8>),
+    QuotedPrintable(Vec<u8>),
+}
+
+fn hex(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+impl Decoder {
+    /// Decode as much of the input seen so far as possible, keeping incomplete sequences for
+    /// the next chunk
+    fn push(&mut self, chunk: &[u8]) -> Result<Bytes, Error> {
+        match *self {
+            Decoder::Base64(ref mut pending) => {
+                pending.extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+
+                let complete = pending.len() - pending.len() % 4;
+                let decoded = base64::decode(&pending[..complete]).map_err(Error::ParseBase64)?;
+                pending.drain(..complete);
+
+                Ok(decoded.into())
+            }
+            Decoder::QuotedPrintable(ref mut pending) => {
+                pending.extend_from_slice(chunk);
+
+                let mut decoded = Vec::with_capacity(pending.len());
+                let mut i = 0;
+
+                while i < pending.len() {
+                    if pending[i] != b'=' {
+                        decoded.push(pending[i]);
+                        i += 1;
+                        continue;
+                    }
+
+                    // Wait for the rest of the escape sequence
+                    if i + 2 >= pending.len() {
+                        break;
+                    }
+
+                    match (pending[i + 1], pending[i + 2]) {
+                        // Soft line breaks
+                        (b'\r', b'\n') => i += 3,
+                        (b'\n', _) => i += 2,
+                        (high, low) => match (hex(high), hex(low)) {
+                            (Some(high), Some(low)) => {
+                                decoded.push(high << 4 | low);
+                                i += 3;
+                            }
+                            // Malformed escapes are kept as they are
+                            _ => {
+                                decoded.push(b'=');
+                                i += 1;
+                            }
+                        },
+                    }
+                }
+
+                pending.drain(..i);
+
+                Ok(decoded.into())
+            }
+        }
+    }
+
+    /// Decode whatever is left once the input has ended
+    fn finish(&mut self) -> Result<Bytes, Error> {
+        match *self {
+            Decoder::Base64(ref mut pending) => {
+                let decoded = base64::decode(&pending[..]).map_err(Error::ParseBase64)?;
+                pending.clear();
+
+                Ok(decoded.into())
+            }
+            Decoder::QuotedPrintable(ref mut pending) => {
+                // A trailing '=' is a soft line break at the end of the input
+                let mut rest = mem::take(pending);
+                if rest.first() == Some(&b'=') && rest[1..].iter().all(|b| b.is_ascii_whitespace())
+                {
+                    rest.clear();
+                }
+
+                Ok(rest.into())
+            }
+        }
+    }
+}
+
+struct Decoded<S> {
+    inner: S,
+    decoder: Decoder,
+    done: bool,
+}
+
+impl<S> Stream for Decoded<S>
+where
+    S: Stream<Item = Bytes, Error = Error>,
+{
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        loop {
+            if self.done {
+                return Ok(Async::Ready(None));
+            }
+
+            let decoded = match self.inner.poll()? {
+                Async::Ready(Some(chunk)) => self.decoder.push(&chunk)?,
+                Async::Ready(None) => {
+                    self.done = true;
+                    self.decoder.finish()?
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            };
+
+            if !decoded.is_empty() {
+                return Ok(Async::Ready(Some(decoded)));
+            }
+        }
+    }
+}
+
+/// The `Content-Transfer-Encoding` of a part
+pub(crate) enum Encoding {
+    Identity,
+    Base64,
+    QuotedPrintable,
+}
+
+impl Encoding {
+    /// Read the encoding from a part's headers
+    ///
+    /// Parts without the header, or with `7bit`, `8bit`, or `binary`, are passed through as
+    /// they are. Any other encoding besides `base64` and `quoted-printable` is rejected.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Result<Self, Error> {
+        let value = match headers.get("content-transfer-encoding") {
+            Some(value) => value,
+            None => return Ok(Encoding::Identity),
+        };
+
+        let encoding = String::from_utf8_lossy(value.as_bytes())
+            .trim()
+            .to_ascii_lowercase();
+
+        match encoding.as_str() {
+            "7bit" | "8bit" | "binary" => Ok(Encoding::Identity),
+            "base64" => Ok(Encoding::Base64),
+            "quoted-printable" => Ok(Encoding::QuotedPrintable),
+            _ => Err(Error::TransferEncoding(encoding)),
+        }
+    }
+
+    /// The most bytes a part can send in this encoding to decode to at most `max` bytes
+    ///
+    /// Base64 takes 4 bytes for every 3, and quoted-printable up to 3 for every byte, with room
+    /// on top for a line break every 76 characters.
+    pub(crate) fn raw_limit(&self, max: usize) -> usize {
+        let encoded = match *self {
+            Encoding::Identity => return max,
+            Encoding::Base64 => (max / 3).saturating_add(1).saturating_mul(4),
+            Encoding::QuotedPrintable => max.saturating_mul(3),
+        };
+
+        // Each line ends with at most `=\r\n`
+        encoded.saturating_add((encoded / 76).saturating_add(1).saturating_mul(3))
+    }
+
+    /// Wrap a part's body so it's decoded as it streams in
+    ///
+    /// Anything reading from the returned stream, including size limits, only sees the decoded
+    /// contents.
+    pub(crate) fn decode<S>(self, stream: S) -> Box<Stream<Item = Bytes, Error = Error>>
+    where
+        S: Stream<Item = Bytes, Error = Error> + 'static,
+    {
+        let decoder = match self {
+            Encoding::Identity => return Box::new(stream),
+            Encoding::Base64 => Decoder::Base64(Vec::new()),
+            Encoding::QuotedPrintable => Decoder::QuotedPrintable(Vec::new()),
+        };
+
+        Box::new(Decoded {
+            inner: stream,
+            decoder,
+            done: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::{stream::iter_ok, Future, Stream};
+
+    use super::Encoding;
+    use crate::error::Error;
+
+    fn decode(encoding: Encoding, chunks: &[&str]) -> Vec<u8> {
+        let chunks: Vec<Bytes> = chunks.iter().map(|chunk| Bytes::from(*chunk)).collect();
+
+        encoding
+            .decode(iter_ok::<_, Error>(chunks))
+            .concat2()
+            .wait()
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn quoted_printable() {
+        let cases: Vec<(&[&str], &str)> = vec![
+            (&["caf=C3=A9"], "café"),
+            (&["a long=\r\nline"], "a longline"),
+            (&["a long=\nline"], "a longline"),
+            (&["a long=", "\r\nline"], "a longline"),
+            (&["a long=\r", "\nline"], "a longline"),
+            (&["caf=C", "3=A9"], "café"),
+            (&["caf=", "C3=A", "9"], "café"),
+            (&["soft break at the end=\r\n"], "soft break at the end"),
+            (&["soft break at the end="], "soft break at the end"),
+            (&["1+1=3D2"], "1+1=2"),
+            (&["bad =ZZ escape"], "bad =ZZ escape"),
+            (&["truncated =4"], "truncated =4"),
+        ];
+
+        for (chunks, expected) in cases {
+            let decoded = decode(Encoding::QuotedPrintable, chunks);
+
+            assert_eq!(
+                String::from_utf8(decoded).unwrap(),
+                expected,
+                "{:?}",
+                chunks
+            );
+        }
+    }
+
+    #[test]
+    fn base64() {
+        let cases: Vec<(&[&str], &str)> = vec![
+            (&["aGVsbG8gd29ybGQ="], "hello world"),
+            (&["aGVs", "bG8g", "d29y", "bGQ="], "hello world"),
+            (&["a", "GVsbG8", "gd29ybG", "Q", "="], "hello world"),
+            (&["aGVsbG8g\r\n", "d29ybGQ=\r\n"], "hello world"),
+            (&["aGVsbG8", " gd29y\nbGQ="], "hello world"),
+            (&[""], ""),
+        ];
+
+        for (chunks, expected) in cases {
+            let decoded = decode(Encoding::Base64, chunks);
+
+            assert_eq!(
+                String::from_utf8(decoded).unwrap(),
+                expected,
+                "{:?}",
+                chunks
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_base64() {
+        let chunks = vec![Bytes::from("aGVsbG8*"), Bytes::from("d29y")];
+
+        let result = Encoding::Base64
+            .decode(iter_ok::<_, Error>(chunks))
+            .concat2()
+            .wait();
+
+        match result {
+            Err(Error::ParseBase64(_)) => (),
+            _ => panic!("expected a base64 error"),
+        }
+    }
+
+    #[test]
+    fn raw_limits() {
+        let max = 1000;
+        let data: Vec<u8> = (0..max).map(|i| i as u8).collect();
+
+        let encoded = base64::encode(&data);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        let base64 = lines.join("\r\n") + "\r\n";
+
+        assert!(base64.len() <= Encoding::Base64.raw_limit(max));
+        assert_eq!(decode(Encoding::Base64, &[&base64]), data);
+
+        let escaped: Vec<String> = data.iter().map(|b| format!("={:02X}", b)).collect();
+        let lines: Vec<String> = escaped.chunks(25).map(|line| line.concat()).collect();
+        let quoted_printable = lines.join("=\r\n") + "=\r\n";
+
+        assert!(quoted_printable.len() <= Encoding::QuotedPrintable.raw_limit(max));
+        assert_eq!(
+            decode(Encoding::QuotedPrintable, &[&quoted_printable]),
+            data
+        );
+
+        assert_eq!(Encoding::Identity.raw_limit(max), max);
+        assert_eq!(Encoding::Base64.raw_limit(usize::MAX), usize::MAX);
+    }
+}
@@ -18,16 +18,32 @@
  */
 
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::VecDeque,
     fmt,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use bytes::Bytes;
+use futures::{Future, IntoFuture};
 use log::trace;
+#[cfg(feature = "regex")]
+use regex::Regex;
 
-use crate::FilenameGenerator;
+use crate::custom::{Custom, CustomValue};
+#[cfg(feature = "chrono")]
+use crate::datetime::{Date, DateTime, Time, Timestamp};
+#[cfg(feature = "serde_json")]
+use crate::json::Json;
+use crate::{
+    error::{Constraint, Error},
+    name::Brackets,
+    sanitize::Sanitizer,
+    AsyncFilenameGenerator, FieldParser, NameParser,
+};
+
+/// The map type used by `Value::Map`, which keeps keys in the order they were first submitted
+pub type ValueMap = indexmap::IndexMap<String, Value>;
 
 /// The result of a succesfull parse through a given multipart stream.
 ///
@@ -36,9 +52,8 @@ use crate::FilenameGenerator;
 /// # Example usage
 ///
 /// ```rust
-/// # use form_data::Value;
-/// # use std::collections::HashMap;
-/// # let mut hm = HashMap::new();
+/// # use form_data::{Value, ValueMap};
+/// # let mut hm = ValueMap::new();
 /// # hm.insert("field-name".to_owned(), Value::Int(32));
 /// # let value = Value::Map(hm);
 /// match value {
@@ -56,37 +71,50 @@ use crate::FilenameGenerator;
 /// ```
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
-    Map(HashMap<String, Value>),
+    Map(ValueMap),
     Array(Vec<Value>),
-    File(String, PathBuf),
+    File {
+        filename: String,
+        stored_as: PathBuf,
+        size: u64,
+    },
     Text(String),
     Int(i64),
     Float(f64),
+    Bool(bool),
+    #[cfg(feature = "chrono")]
+    Date(chrono::NaiveDate),
+    #[cfg(feature = "chrono")]
+    Time(chrono::NaiveTime),
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::NaiveDateTime),
+    #[cfg(feature = "chrono")]
+    Timestamp(chrono::DateTime<chrono::Utc>),
+    #[cfg(feature = "serde_json")]
+    Json(serde_json::Value),
+    Custom(CustomValue),
     Bytes(Bytes),
 }
 
 impl Value {
-    pub(crate) fn merge(&mut self, rhs: Self) {
-        match (self, rhs) {
-            (&mut Value::Map(ref mut hm), Value::Map(ref other)) => {
-                other.into_iter().fold(hm, |hm, (key, value)| {
-                    if let Some(v) = hm.get_mut(key) {
-                        v.merge(value.clone());
-                    } else {
-                        hm.insert(key.to_owned(), value.clone());
-                    }
-
-                    hm
-                });
+    pub(crate) fn stored_files(&self, files: &mut Vec<PathBuf>) {
+        match *self {
+            Value::Map(ref hm) => {
+                for value in hm.values() {
+                    value.stored_files(files);
+                }
             }
-            (&mut Value::Array(ref mut v), Value::Array(ref other)) => {
-                v.extend(other.clone());
+            Value::Array(ref vec) => {
+                for value in vec {
+                    value.stored_files(files);
+                }
             }
+            Value::File { ref stored_as, .. } => files.push(stored_as.clone()),
             _ => (),
         }
     }
 
-    pub fn map(self) -> Option<HashMap<String, Value>> {
+    pub fn map(self) -> Option<ValueMap> {
         match self {
             Value::Map(map) => Some(map),
             _ => None,
@@ -102,7 +130,11 @@ impl Value {
 
     pub fn file(self) -> Option<(String, PathBuf)> {
         match self {
-            Value::File(name, path) => Some((name, path)),
+            Value::File {
+                filename,
+                stored_as,
+                ..
+            } => Some((filename, stored_as)),
             _ => None,
         }
     }
@@ -128,12 +160,195 @@ impl Value {
         }
     }
 
+    pub fn bool(self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn date(self) -> Option<chrono::NaiveDate> {
+        match self {
+            Value::Date(date) => Some(date),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn time(self) -> Option<chrono::NaiveTime> {
+        match self {
+            Value::Time(time) => Some(time),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn datetime(self) -> Option<chrono::NaiveDateTime> {
+        match self {
+            Value::DateTime(datetime) => Some(datetime),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn timestamp(self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self {
+            Value::Timestamp(timestamp) => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub fn json(self) -> Option<serde_json::Value> {
+        match self {
+            Value::Json(json) => Some(json),
+            _ => None,
+        }
+    }
+
+    pub fn custom(self) -> Option<CustomValue> {
+        match self {
+            Value::Custom(custom) => Some(custom),
+            _ => None,
+        }
+    }
+
     pub fn bytes(self) -> Option<Bytes> {
         match self {
             Value::Bytes(bytes) => Some(bytes),
             _ => None,
         }
     }
+
+    /// Borrow the contained map, if this is a `Value::Map`
+    pub fn as_map(&self) -> Option<&ValueMap> {
+        match *self {
+            Value::Map(ref map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Borrow the contained array, if this is a `Value::Array`
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref vec) => Some(vec),
+            _ => None,
+        }
+    }
+
+    /// Borrow the original filename and stored path, if this is a `Value::File`
+    pub fn as_file(&self) -> Option<(&str, &Path)> {
+        match *self {
+            Value::File {
+                ref filename,
+                ref stored_as,
+                ..
+            } => Some((filename, stored_as)),
+            _ => None,
+        }
+    }
+
+    /// Get the number of bytes written, if this is a `Value::File`
+    pub fn file_size(&self) -> Option<u64> {
+        match *self {
+            Value::File { size, .. } => Some(size),
+            _ => None,
+        }
+    }
+
+    /// Borrow the contained text, if this is a `Value::Text`
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            Value::Text(ref text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Get the contained int, if this is a `Value::Int`
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            Value::Int(int) => Some(int),
+            _ => None,
+        }
+    }
+
+    /// Get the contained float, if this is a `Value::Float`
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            Value::Float(float) => Some(float),
+            _ => None,
+        }
+    }
+
+    /// Get the contained bool, if this is a `Value::Bool`
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Get the contained date, if this is a `Value::Date`
+    #[cfg(feature = "chrono")]
+    pub fn as_date(&self) -> Option<chrono::NaiveDate> {
+        match *self {
+            Value::Date(date) => Some(date),
+            _ => None,
+        }
+    }
+
+    /// Get the contained time, if this is a `Value::Time`
+    #[cfg(feature = "chrono")]
+    pub fn as_time(&self) -> Option<chrono::NaiveTime> {
+        match *self {
+            Value::Time(time) => Some(time),
+            _ => None,
+        }
+    }
+
+    /// Get the contained date and time, if this is a `Value::DateTime`
+    #[cfg(feature = "chrono")]
+    pub fn as_datetime(&self) -> Option<chrono::NaiveDateTime> {
+        match *self {
+            Value::DateTime(datetime) => Some(datetime),
+            _ => None,
+        }
+    }
+
+    /// Get the contained timestamp, if this is a `Value::Timestamp`
+    #[cfg(feature = "chrono")]
+    pub fn as_timestamp(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match *self {
+            Value::Timestamp(timestamp) => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    /// Borrow the contained JSON, if this is a `Value::Json`
+    #[cfg(feature = "serde_json")]
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match *self {
+            Value::Json(ref json) => Some(json),
+            _ => None,
+        }
+    }
+
+    /// Borrow the contained custom value, if this is a `Value::Custom`
+    pub fn as_custom(&self) -> Option<&CustomValue> {
+        match *self {
+            Value::Custom(ref custom) => Some(custom),
+            _ => None,
+        }
+    }
+
+    /// Borrow the contained bytes, if this is a `Value::Bytes`
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match *self {
+            Value::Bytes(ref bytes) => Some(bytes),
+            _ => None,
+        }
+    }
 }
 
 impl From<MultipartContent> for Value {
@@ -142,10 +357,28 @@ impl From<MultipartContent> for Value {
             MultipartContent::File {
                 filename,
                 stored_as,
-            } => Value::File(filename, stored_as),
+                size,
+                ..
+            } => Value::File {
+                filename,
+                stored_as,
+                size,
+            },
             MultipartContent::Text(string) => Value::Text(string),
             MultipartContent::Int(i) => Value::Int(i),
             MultipartContent::Float(f) => Value::Float(f),
+            MultipartContent::Bool(b) => Value::Bool(b),
+            #[cfg(feature = "chrono")]
+            MultipartContent::Date(d) => Value::Date(d),
+            #[cfg(feature = "chrono")]
+            MultipartContent::Time(t) => Value::Time(t),
+            #[cfg(feature = "chrono")]
+            MultipartContent::DateTime(dt) => Value::DateTime(dt),
+            #[cfg(feature = "chrono")]
+            MultipartContent::Timestamp(ts) => Value::Timestamp(ts),
+            #[cfg(feature = "serde_json")]
+            MultipartContent::Json(value) => value,
+            MultipartContent::Custom(custom) => Value::Custom(custom),
             MultipartContent::Bytes(b) => Value::Bytes(b),
         }
     }
@@ -155,11 +388,25 @@ impl From<MultipartContent> for Value {
 #[derive(Clone)]
 pub enum Field {
     Array(Array),
-    File(Arc<FilenameGenerator>),
+    File(Arc<AsyncFilenameGenerator>),
     Map(Map),
-    Int,
-    Float,
-    Text,
+    Dict(Dict),
+    Int(Int),
+    Float(Float),
+    Text(Text),
+    Bool(Bool),
+    Choice(Choice),
+    #[cfg(feature = "chrono")]
+    Date(Date),
+    #[cfg(feature = "chrono")]
+    Time(Time),
+    #[cfg(feature = "chrono")]
+    DateTime(DateTime),
+    #[cfg(feature = "chrono")]
+    Timestamp(Timestamp),
+    #[cfg(feature = "serde_json")]
+    Json(Json),
+    Custom(Custom),
     Bytes,
 }
 
@@ -169,9 +416,23 @@ impl fmt::Debug for Field {
             Field::Array(ref arr) => write!(f, "Array({:?})", arr),
             Field::File(_) => write!(f, "File(filename_generator)"),
             Field::Map(ref map) => write!(f, "Map({:?})", map),
-            Field::Int => write!(f, "Int"),
-            Field::Float => write!(f, "Float"),
-            Field::Text => write!(f, "Text"),
+            Field::Dict(ref dict) => write!(f, "Dict({:?})", dict),
+            Field::Int(ref i) => write!(f, "Int({:?})", i),
+            Field::Float(ref fl) => write!(f, "Float({:?})", fl),
+            Field::Text(ref t) => write!(f, "Text({:?})", t),
+            Field::Bool(ref b) => write!(f, "Bool({:?})", b),
+            Field::Choice(ref c) => write!(f, "Choice({:?})", c),
+            #[cfg(feature = "chrono")]
+            Field::Date(ref d) => write!(f, "Date({:?})", d),
+            #[cfg(feature = "chrono")]
+            Field::Time(ref t) => write!(f, "Time({:?})", t),
+            #[cfg(feature = "chrono")]
+            Field::DateTime(ref dt) => write!(f, "DateTime({:?})", dt),
+            #[cfg(feature = "chrono")]
+            Field::Timestamp(ref ts) => write!(f, "Timestamp({:?})", ts),
+            #[cfg(feature = "serde_json")]
+            Field::Json(ref json) => write!(f, "Json({:?})", json),
+            Field::Custom(ref c) => write!(f, "{:?}", c),
             Field::Bytes => write!(f, "Bytes"),
         }
     }
@@ -184,17 +445,19 @@ impl Field {
     /// that each key/file pair will have it's own name-generator, so sharing a name-generator
     /// between fields is up to the user.
     ///
+    /// Either a `FilenameGenerator` or an `AsyncFilenameGenerator` can be used.
+    ///
     /// # Example
     /// ```rust
     /// # extern crate mime;
     /// # extern crate form_data;
     /// # use std::path::{Path, PathBuf};
-    /// # use form_data::{Form, Field, FilenameGenerator};
+    /// # use form_data::{Form, Field, FilenameGenerator, UploadContext};
     ///
     /// struct Gen;
     ///
     /// impl FilenameGenerator for Gen {
-    ///     fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
+    ///     fn next_filename(&self, _: &UploadContext) -> Option<PathBuf> {
     ///         Some(AsRef::<Path>::as_ref("path.png").to_owned())
     ///     }
     /// }
@@ -207,13 +470,15 @@ impl Field {
     /// ```
     pub fn file<T>(gen: T) -> Self
     where
-        T: FilenameGenerator + 'static,
+        T: AsyncFilenameGenerator + 'static,
     {
         Field::File(Arc::new(gen))
     }
 
     /// Add a Text field to a form
     ///
+    /// See the `Text` documentation for the constraints that can be placed on the field.
+    ///
     /// # Example
     /// ```rust
     /// # extern crate form_data;
@@ -221,8 +486,8 @@ impl Field {
     /// # fn main() {
     /// let form = Form::new().field("text-field", Field::text());
     /// # }
-    pub fn text() -> Self {
-        Field::Text
+    pub fn text() -> Text {
+        Text::new()
     }
 
     /// Add an Int field to a form
@@ -235,8 +500,8 @@ impl Field {
     /// let form = Form::new().field("int-field", Field::int());
     /// # }
     /// ```
-    pub fn int() -> Self {
-        Field::Int
+    pub fn int() -> Int {
+        Int::new()
     }
 
     /// Add a Float field to a form
@@ -249,8 +514,8 @@ impl Field {
     /// let form = Form::new().field("float-field", Field::float());
     /// # }
     /// ```
-    pub fn float() -> Self {
-        Field::Float
+    pub fn float() -> Float {
+        Float::new()
     }
 
     /// Add a Bytes field to a form
@@ -267,8 +532,139 @@ impl Field {
         Field::Bytes
     }
 
+    /// Add a Bool field to a form
+    ///
+    /// By default, the values `on`, `true`, `yes`, and `1` are parsed as `true`, and the values
+    /// `off`, `false`, `no`, and `0` are parsed as `false`. See the `Bool` documentation for how
+    /// to change this.
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("bool-field", Field::bool());
+    /// # }
+    /// ```
+    pub fn bool() -> Bool {
+        Bool::new()
+    }
+
+    /// Add a Choice field to a form
+    ///
+    /// The provided value must match one of the given choices, such as the options of an HTML
+    /// `select` input. See the `Choice` documentation for more options.
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field(
+    ///     "choice-field",
+    ///     Field::choice(&["draft", "published", "archived"]),
+    /// );
+    /// # }
+    /// ```
+    pub fn choice(choices: &[&str]) -> Choice {
+        Choice::new(choices)
+    }
+
+    /// Add a Date field to a form
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("date-field", Field::date());
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn date() -> Date {
+        Date::new()
+    }
+
+    /// Add a Time field to a form
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("time-field", Field::time());
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn time() -> Time {
+        Time::new()
+    }
+
+    /// Add a DateTime field to a form
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("datetime-field", Field::datetime());
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn datetime() -> DateTime {
+        DateTime::new()
+    }
+
+    /// Add a Unix Timestamp field to a form
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("timestamp-field", Field::timestamp());
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn timestamp() -> Timestamp {
+        Timestamp::new()
+    }
+
+    /// Add a JSON field to a form
+    ///
+    /// The field is buffered (subject to the form's `max_field_size`) and parsed as JSON. See the
+    /// `Json` documentation for how the result is represented.
+    ///
+    /// # Example
+    /// ```rust
+    /// # extern crate form_data;
+    /// # use form_data::{Form, Field};
+    /// # fn main() {
+    /// let form = Form::new().field("json-field", Field::json());
+    /// # }
+    /// ```
+    #[cfg(feature = "serde_json")]
+    pub fn json() -> Json {
+        Json::new()
+    }
+
+    /// Add a field parsed by a custom `FieldParser` to a form
+    ///
+    /// See the `FieldParser` documentation for an example.
+    pub fn custom<P>(parser: P) -> Self
+    where
+        P: FieldParser + 'static,
+    {
+        Field::Custom(Custom::new(parser))
+    }
+
     /// Add an Array to a form
     ///
+    /// Elements are submitted as `array-field[]`, which appends a new element, or with an
+    /// explicit position as `array-field[0]`. Indexed elements are ordered by their index, and
+    /// parts sharing an index, such as `items[0][name]` and `items[0][qty]`, build the same
+    /// element.
+    ///
     /// # Example
     /// ```rust
     /// # extern crate form_data;
@@ -281,8 +677,11 @@ impl Field {
     ///     );
     /// # }
     /// ```
-    pub fn array(field: Field) -> Self {
-        Field::Array(Array::new(field))
+    pub fn array<F>(field: F) -> Self
+    where
+        F: Into<Field>,
+    {
+        Field::Array(Array::new(field.into()))
     }
 
     /// Add a Map to a form
@@ -306,11 +705,34 @@ impl Field {
         Map::new()
     }
 
-    fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    /// Add a Dict to a form
+    ///
+    /// Unlike a Map, a Dict accepts any key, so `meta[color]` and `meta[size]` both end up in
+    /// the same `Value::Map`. Every value is parsed with the provided field.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Form, Field};
+    /// #
+    /// let form = Form::new().field("meta", Field::dict(Field::text()).max_keys(16));
+    /// ```
+    pub fn dict<F>(field: F) -> Dict
+    where
+        F: Into<Field>,
+    {
+        Dict::new(field.into())
+    }
+
+    pub(crate) fn valid_field(
+        &self,
+        name: VecDeque<NamePart>,
+        path: &mut Vec<NamePart>,
+    ) -> Option<FieldTerminator> {
         trace!("Checking {:?} and {:?}", self, name);
         match *self {
-            Field::Array(ref arr) => arr.valid_field(name),
-            Field::Map(ref map) => map.valid_field(name),
+            Field::Array(ref arr) => arr.valid_field(name, path),
+            Field::Map(ref map) => map.valid_field(name, path),
+            Field::Dict(ref dict) => dict.valid_field(name, path),
             Field::File(ref gen) => {
                 if name.is_empty() {
                     Some(FieldTerminator::File(Arc::clone(gen)))
@@ -318,75 +740,761 @@ impl Field {
                     None
                 }
             }
-            Field::Int => {
+            Field::Int(ref i) => {
                 if name.is_empty() {
-                    Some(FieldTerminator::Int)
+                    Some(FieldTerminator::Int(i.clone()))
                 } else {
                     None
                 }
             }
-            Field::Float => {
+            Field::Float(ref f) => {
                 if name.is_empty() {
-                    Some(FieldTerminator::Float)
+                    Some(FieldTerminator::Float(f.clone()))
                 } else {
                     None
                 }
             }
-            Field::Text => {
+            Field::Text(ref t) => {
                 if name.is_empty() {
-                    Some(FieldTerminator::Text)
+                    Some(FieldTerminator::Text(t.clone()))
                 } else {
                     None
                 }
             }
-            Field::Bytes => {
+            Field::Bool(ref b) => {
                 if name.is_empty() {
-                    Some(FieldTerminator::Bytes)
+                    Some(FieldTerminator::Bool(b.clone()))
                 } else {
                     None
                 }
             }
-        }
-    }
-}
-
-/// A definition of an array of type `Field` to be parsed from form data.
-///
-/// The `Array` type should only be constructed in the context of a Form. See the `Form`
-/// documentation for more information.
-#[derive(Debug, Clone)]
-pub struct Array {
-    inner: Box<Field>,
-}
-
-impl Array {
-    fn new(field: Field) -> Self {
-        Array {
-            inner: Box::new(field),
-        }
-    }
-
-    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator> {
-        trace!("Checking {:?} and {:?}", self, name);
-        match name.pop_front() {
-            Some(name_part) => match name_part {
-                NamePart::Array => self.inner.valid_field(name),
-                _ => None,
-            },
-            None => None,
-        }
-    }
-}
-
-/// A definition of key-value pairs to be parsed from form data.
-#[derive(Debug, Clone)]
-pub struct Map {
-    inner: Vec<(String, Field)>,
+            Field::Choice(ref c) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Choice(c.clone()))
+                } else {
+                    None
+                }
+            }
+            #[cfg(feature = "chrono")]
+            Field::Date(ref d) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Date(d.clone()))
+                } else {
+                    None
+                }
+            }
+            #[cfg(feature = "chrono")]
+            Field::Time(ref t) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Time(t.clone()))
+                } else {
+                    None
+                }
+            }
+            #[cfg(feature = "chrono")]
+            Field::DateTime(ref dt) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::DateTime(dt.clone()))
+                } else {
+                    None
+                }
+            }
+            #[cfg(feature = "chrono")]
+            Field::Timestamp(ref ts) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Timestamp(ts.clone()))
+                } else {
+                    None
+                }
+            }
+            #[cfg(feature = "serde_json")]
+            Field::Json(ref json) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Json(json.clone()))
+                } else {
+                    None
+                }
+            }
+            Field::Custom(ref c) => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Custom(c.clone()))
+                } else {
+                    None
+                }
+            }
+            Field::Bytes => {
+                if name.is_empty() {
+                    Some(FieldTerminator::Bytes)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn default_value(&self) -> Option<Value> {
+        match *self {
+            Field::Bool(ref b) if b.default_false => Some(Value::Bool(false)),
+            _ => None,
+        }
+    }
+
+    fn fill_defaults(&self, value: &mut Value) {
+        match (self, value) {
+            (Field::Map(map), Value::Map(hm)) => map.fill_defaults(hm),
+            (Field::Dict(dict), Value::Map(hm)) => {
+                for value in hm.values_mut() {
+                    dict.inner.fill_defaults(value);
+                }
+            }
+            (Field::Array(arr), Value::Array(values)) => {
+                for value in values.iter_mut() {
+                    arr.inner.fill_defaults(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn check_keys(&self, value: &Value) -> Result<(), Error> {
+        match (self, value) {
+            (Field::Map(map), Value::Map(hm)) => map.check_keys(hm),
+            (Field::Dict(dict), Value::Map(hm)) => {
+                dict.check_len(hm.len())?;
+
+                for value in hm.values() {
+                    dict.inner.check_keys(value)?;
+                }
+
+                Ok(())
+            }
+            (Field::Array(arr), Value::Array(values)) => {
+                for value in values {
+                    arr.inner.check_keys(value)?;
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<Map> for Field {
+    fn from(map: Map) -> Self {
+        map.finalize()
+    }
+}
+
+impl From<Int> for Field {
+    fn from(i: Int) -> Self {
+        i.finalize()
+    }
+}
+
+impl From<Float> for Field {
+    fn from(f: Float) -> Self {
+        f.finalize()
+    }
+}
+
+impl From<Text> for Field {
+    fn from(t: Text) -> Self {
+        t.finalize()
+    }
+}
+
+impl From<Bool> for Field {
+    fn from(b: Bool) -> Self {
+        b.finalize()
+    }
+}
+
+/// A definition of an integer field
+///
+/// The `Int` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Int {
+    min: Option<i64>,
+    max: Option<i64>,
+}
+
+impl Int {
+    fn new() -> Self {
+        Int {
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Set the smallest value allowed for this field
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::int().min(0).max(100);
+    /// ```
+    pub fn min(mut self, min: i64) -> Self {
+        self.min = Some(min);
+
+        self
+    }
+
+    /// Set the largest value allowed for this field
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::int().min(0).max(100);
+    /// ```
+    pub fn max(mut self, max: i64) -> Self {
+        self.max = Some(max);
+
+        self
+    }
+
+    /// Finalize the int into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Int(self)
+    }
+
+    pub(crate) fn parse(&self, name: &str, string: &str) -> Result<i64, Error> {
+        let int = string.parse::<i64>().map_err(Error::ParseInt)?;
+
+        if let Some(min) = self.min {
+            if int < min {
+                return Err(Error::Constraint(name.to_owned(), Constraint::MinInt(min)));
+            }
+        }
+
+        if let Some(max) = self.max {
+            if int > max {
+                return Err(Error::Constraint(name.to_owned(), Constraint::MaxInt(max)));
+            }
+        }
+
+        Ok(int)
+    }
+}
+
+/// A definition of a floating-point field
+///
+/// The `Float` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Float {
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Float {
+    fn new() -> Self {
+        Float {
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Set the smallest value allowed for this field
+    ///
+    /// When a bound is set, `NaN` is rejected.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::float().min(0.0).max(1.0);
+    /// ```
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+
+        self
+    }
+
+    /// Set the largest value allowed for this field
+    ///
+    /// When a bound is set, `NaN` is rejected.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::float().min(0.0).max(1.0);
+    /// ```
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+
+        self
+    }
+
+    /// Finalize the float into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Float(self)
+    }
+
+    pub(crate) fn parse(&self, name: &str, string: &str) -> Result<f64, Error> {
+        let float = string.parse::<f64>().map_err(Error::ParseFloat)?;
+
+        if let Some(min) = self.min {
+            if float.is_nan() || float < min {
+                return Err(Error::Constraint(
+                    name.to_owned(),
+                    Constraint::MinFloat(min),
+                ));
+            }
+        }
+
+        if let Some(max) = self.max {
+            if float.is_nan() || float > max {
+                return Err(Error::Constraint(
+                    name.to_owned(),
+                    Constraint::MaxFloat(max),
+                ));
+            }
+        }
+
+        Ok(float)
+    }
+}
+
+/// A definition of a text field
+///
+/// The `Text` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Text {
+    trim: bool,
+    non_empty: bool,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    #[cfg(feature = "regex")]
+    pattern: Option<Regex>,
+}
+
+impl Text {
+    fn new() -> Self {
+        Text {
+            trim: false,
+            non_empty: false,
+            min_len: None,
+            max_len: None,
+            #[cfg(feature = "regex")]
+            pattern: None,
+        }
+    }
+
+    /// Remove leading and trailing whitespace from the value
+    ///
+    /// Trimming happens before any other constraints are checked, and the trimmed value is the
+    /// one that ends up in the resulting `Value`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::text().trim().non_empty();
+    /// ```
+    pub fn trim(mut self) -> Self {
+        self.trim = true;
+
+        self
+    }
+
+    /// Reject empty values
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::text().trim().non_empty();
+    /// ```
+    pub fn non_empty(mut self) -> Self {
+        self.non_empty = true;
+
+        self
+    }
+
+    /// Set the minimum length of the value, in characters
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::text().min_len(8).max_len(64);
+    /// ```
+    pub fn min_len(mut self, min: usize) -> Self {
+        self.min_len = Some(min);
+
+        self
+    }
+
+    /// Set the maximum length of the value, in characters
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::text().min_len(8).max_len(64);
+    /// ```
+    pub fn max_len(mut self, max: usize) -> Self {
+        self.max_len = Some(max);
+
+        self
+    }
+
+    /// Require the value to match a regular expression
+    ///
+    /// Unlike HTML's `pattern` attribute, the expression isn't implicitly anchored, so use `^`
+    /// and `$` to match the whole value. This requires the `regex` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// # use regex::Regex;
+    /// #
+    /// Field::text().pattern(Regex::new("^[a-z0-9-]+$").unwrap());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn pattern(mut self, pattern: Regex) -> Self {
+        self.pattern = Some(pattern);
+
+        self
+    }
+
+    /// Finalize the text into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Text(self)
+    }
+
+    pub(crate) fn parse(&self, name: &str, string: String) -> Result<String, Error> {
+        let string = if self.trim {
+            string.trim().to_owned()
+        } else {
+            string
+        };
+
+        if self.non_empty && string.is_empty() {
+            return Err(Error::Constraint(name.to_owned(), Constraint::NonEmpty));
+        }
+
+        let len = string.chars().count();
+
+        if let Some(min) = self.min_len {
+            if len < min {
+                return Err(Error::Constraint(name.to_owned(), Constraint::MinLen(min)));
+            }
+        }
+
+        if let Some(max) = self.max_len {
+            if len > max {
+                return Err(Error::Constraint(name.to_owned(), Constraint::MaxLen(max)));
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        {
+            if let Some(ref pattern) = self.pattern {
+                if !pattern.is_match(&string) {
+                    return Err(Error::Constraint(
+                        name.to_owned(),
+                        Constraint::Pattern(pattern.as_str().to_owned()),
+                    ));
+                }
+            }
+        }
+
+        Ok(string)
+    }
+}
+
+/// A definition of a boolean field, such as an HTML checkbox
+///
+/// The `Bool` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Bool {
+    truthy: Vec<String>,
+    falsy: Vec<String>,
+    default_false: bool,
+}
+
+impl Bool {
+    fn new() -> Self {
+        Bool {
+            truthy: vec!["on", "true", "yes", "1"]
+                .into_iter()
+                .map(From::from)
+                .collect(),
+            falsy: vec!["off", "false", "no", "0"]
+                .into_iter()
+                .map(From::from)
+                .collect(),
+            default_false: false,
+        }
+    }
+
+    /// Set the values that are parsed as `true`
+    ///
+    /// Values are compared without regard to ASCII case.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::bool().truthy(&["y", "checked"]);
+    /// ```
+    pub fn truthy(mut self, values: &[&str]) -> Self {
+        self.truthy = values.iter().map(|v| (*v).to_owned()).collect();
+
+        self
+    }
+
+    /// Set the values that are parsed as `false`
+    ///
+    /// Values are compared without regard to ASCII case.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::bool().falsy(&["n", "unchecked"]);
+    /// ```
+    pub fn falsy(mut self, values: &[&str]) -> Self {
+        self.falsy = values.iter().map(|v| (*v).to_owned()).collect();
+
+        self
+    }
+
+    /// Treat this field as `false` when it is missing from the request
+    ///
+    /// Browsers don't submit unchecked checkboxes at all, so this makes an absent checkbox show
+    /// up in the resulting `Value` as `Value::Bool(false)`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, Form};
+    /// #
+    /// Form::new().field("remember-me", Field::bool().default_false());
+    /// ```
+    pub fn default_false(mut self) -> Self {
+        self.default_false = true;
+
+        self
+    }
+
+    /// Finalize the bool into a `Field`, so it can be added to a Form
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::bool().default_false().finalize();
+    /// ```
+    pub fn finalize(self) -> Field {
+        Field::Bool(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Option<bool> {
+        let value = value.trim();
+
+        if self.truthy.iter().any(|t| t.eq_ignore_ascii_case(value)) {
+            Some(true)
+        } else if self.falsy.iter().any(|f| f.eq_ignore_ascii_case(value)) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<Choice> for Field {
+    fn from(c: Choice) -> Self {
+        c.finalize()
+    }
+}
+
+/// A definition of a field that must match one of a set of choices
+///
+/// By default, a matching value is stored as `Value::Text`, using the spelling provided to
+/// `Field::choice`. With the `serde` feature enabled, text values can be deserialized directly
+/// into a user-defined enum.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    choices: Vec<String>,
+    case_sensitive: bool,
+    indexed: bool,
+}
+
+impl Choice {
+    fn new(choices: &[&str]) -> Self {
+        Choice {
+            choices: choices.iter().map(|c| (*c).to_owned()).collect(),
+            case_sensitive: true,
+            indexed: false,
+        }
+    }
+
+    /// Accept choices without regard to case
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::choice(&["draft", "published"]).case_insensitive();
+    /// ```
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_sensitive = false;
+
+        self
+    }
+
+    /// Store the position of the matched choice as a `Value::Int` instead of its text
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::choice(&["low", "medium", "high"]).indexed();
+    /// ```
+    pub fn indexed(mut self) -> Self {
+        self.indexed = true;
+
+        self
+    }
+
+    /// Finalize the choice into a `Field`, so it can be added to a Form
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::choice(&["draft", "published"]).finalize();
+    /// ```
+    pub fn finalize(self) -> Field {
+        Field::Choice(self)
+    }
+
+    pub(crate) fn parse(&self, value: &str) -> Result<MultipartContent, Error> {
+        let position = if self.case_sensitive {
+            self.choices.iter().position(|c| c == value)
+        } else {
+            let value = value.to_lowercase();
+            self.choices.iter().position(|c| c.to_lowercase() == value)
+        };
+
+        match position {
+            Some(index) if self.indexed => Ok(MultipartContent::Int(index as i64)),
+            Some(index) => Ok(MultipartContent::Text(self.choices[index].clone())),
+            None => Err(Error::Choice(value.to_owned(), self.choices.join(", "))),
+        }
+    }
+}
+
+/// A definition of an array of type `Field` to be parsed from form data.
+///
+/// The `Array` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Array {
+    inner: Box<Field>,
+}
+
+impl Array {
+    fn new(field: Field) -> Self {
+        Array {
+            inner: Box::new(field),
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn field(&self) -> &Field {
+        &self.inner
+    }
+
+    fn valid_field(
+        &self,
+        mut name: VecDeque<NamePart>,
+        path: &mut Vec<NamePart>,
+    ) -> Option<FieldTerminator> {
+        trace!("Checking {:?} and {:?}", self, name);
+        match name.pop_front() {
+            Some(name_part) => match name_part {
+                NamePart::Array | NamePart::Index(_) => {
+                    path.push(name_part);
+                    self.inner.valid_field(name, path)
+                }
+                _ => None,
+            },
+            None => None,
+        }
+    }
+}
+
+/// What to do with fields that aren't defined in a `Form` or `Map`
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnknownFields {
+    /// Fail the request with `Error::FieldType`. This is the default.
+    Reject,
+    /// Read and discard the field's contents. Size limits still apply to skipped fields, and
+    /// they count towards the form's `max_fields`.
+    Skip,
+    /// Keep the field's contents as text (or bytes, if they aren't valid UTF-8) inside a map
+    /// stored under the provided key. The key shouldn't be used by any defined field.
+    Collect(String),
+}
+
+/// What to do when the same name is submitted more than once for a single value
+///
+/// Names that add to an array, such as `tags[]`, are never duplicates. Files uploaded for values
+/// that aren't kept are removed. A name that treats an earlier value as a different kind of
+/// value, like `user` followed by `user[name]`, fails the request with `Error::FieldShape`
+/// under any policy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateFields {
+    /// Fail the request with `Error::DuplicateField`
+    Reject,
+    /// Keep the value that was submitted first. This is the default.
+    KeepFirst,
+    /// Keep the value that was submitted last
+    KeepLast,
+    /// Collect every submitted value into a `Value::Array`, even if the field isn't defined as
+    /// an array
+    Array,
+}
+
+/// A definition of key-value pairs to be parsed from form data.
+#[derive(Debug, Clone)]
+pub struct Map {
+    inner: Vec<(String, Field)>,
+    unknown: UnknownFields,
 }
 
 impl Map {
     fn new() -> Self {
-        Map { inner: Vec::new() }
+        Map {
+            inner: Vec::new(),
+            unknown: UnknownFields::Reject,
+        }
+    }
+
+    /// Set how keys that aren't defined in this map are handled
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, UnknownFields};
+    /// #
+    /// Field::map()
+    ///     .field("sub-field", Field::text())
+    ///     .unknown_fields(UnknownFields::Collect("extra".to_owned()));
+    /// ```
+    pub fn unknown_fields(mut self, policy: UnknownFields) -> Self {
+        self.unknown = policy;
+
+        self
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn unknown(&self) -> &UnknownFields {
+        &self.unknown
     }
 
     /// Add a `Field` to a map
@@ -399,8 +1507,11 @@ impl Map {
     ///     .field("sub-field-two", Field::text())
     ///     .finalize();
     /// ```
-    pub fn field(mut self, key: &str, value: Field) -> Self {
-        self.inner.push((key.to_owned(), value));
+    pub fn field<F>(mut self, key: &str, value: F) -> Self
+    where
+        F: Into<Field>,
+    {
+        self.inner.push((key.to_owned(), value.into()));
 
         self
     }
@@ -418,19 +1529,176 @@ impl Map {
         Field::Map(self)
     }
 
-    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    pub(crate) fn get(&self, key: &str) -> Option<&Field> {
+        self.inner
+            .iter()
+            .find(|(item, _)| item == key)
+            .map(|(_, field)| field)
+    }
+
+    fn valid_field(
+        &self,
+        mut name: VecDeque<NamePart>,
+        path: &mut Vec<NamePart>,
+    ) -> Option<FieldTerminator> {
         trace!("Checking {:?} and {:?}", self, name);
-        match name.pop_front() {
-            Some(name_part) => match name_part {
-                NamePart::Map(part_name) => self
-                    .inner
-                    .iter()
-                    .find(|&&(ref item, _)| *item == part_name)
-                    .and_then(|&(_, ref field)| field.valid_field(name)),
-                _ => None,
-            },
-            None => None,
+        let part_name = match name.pop_front() {
+            Some(NamePart::Map(part_name)) => part_name,
+            // A numeric segment addresses a key when the schema expects a map here
+            Some(NamePart::Index(index)) => index.to_string(),
+            _ => return None,
+        };
+
+        if let Some(field) = self.get(&part_name) {
+            path.push(NamePart::Map(part_name));
+            return field.valid_field(name, path);
+        }
+
+        match self.unknown {
+            UnknownFields::Reject => None,
+            UnknownFields::Skip => Some(FieldTerminator::Skip),
+            UnknownFields::Collect(ref key) => {
+                path.push(NamePart::Map(key.to_owned()));
+                path.push(NamePart::Map(part_name));
+                path.extend(name);
+                Some(FieldTerminator::Collect)
+            }
+        }
+    }
+
+    pub(crate) fn fill_defaults(&self, hm: &mut ValueMap) {
+        for (key, field) in &self.inner {
+            if let Some(value) = hm.get_mut(key) {
+                field.fill_defaults(value);
+                continue;
+            }
+
+            if let Some(value) = field.default_value() {
+                hm.insert(key.to_owned(), value);
+            }
+        }
+    }
+
+    fn check_keys(&self, hm: &ValueMap) -> Result<(), Error> {
+        for (key, field) in &self.inner {
+            if let Some(value) = hm.get(key) {
+                field.check_keys(value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A definition of key-value pairs with arbitrary keys, where every value shares one field type
+///
+/// The `Dict` type should only be constructed in the context of a Form. See the `Form`
+/// documentation for more information.
+#[derive(Debug, Clone)]
+pub struct Dict {
+    inner: Box<Field>,
+    #[cfg(feature = "regex")]
+    key_pattern: Option<Regex>,
+    max_keys: Option<usize>,
+}
+
+impl Dict {
+    fn new(field: Field) -> Self {
+        Dict {
+            inner: Box::new(field),
+            #[cfg(feature = "regex")]
+            key_pattern: None,
+            max_keys: None,
+        }
+    }
+
+    /// Require every key to match a regular expression
+    ///
+    /// A key that doesn't match fails the request with `Error::FieldType`, whatever the form's
+    /// `UnknownFields` policy is. This requires the `regex` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// # use regex::Regex;
+    /// #
+    /// Field::dict(Field::text()).key_pattern(Regex::new("^[a-z_]+$").unwrap());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn key_pattern(mut self, pattern: Regex) -> Self {
+        self.key_pattern = Some(pattern);
+
+        self
+    }
+
+    /// Set the maximum number of keys the dict may contain
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Field;
+    /// #
+    /// Field::dict(Field::text()).max_keys(20);
+    /// ```
+    pub fn max_keys(mut self, max: usize) -> Self {
+        self.max_keys = Some(max);
+
+        self
+    }
+
+    /// Finalize the dict into a `Field`, so it can be added to a Form
+    pub fn finalize(self) -> Field {
+        Field::Dict(self)
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn field(&self) -> &Field {
+        &self.inner
+    }
+
+    #[cfg(feature = "regex")]
+    pub(crate) fn valid_key(&self, key: &str) -> bool {
+        self.key_pattern
+            .as_ref()
+            .map(|pattern| pattern.is_match(key))
+            .unwrap_or(true)
+    }
+
+    #[cfg(not(feature = "regex"))]
+    pub(crate) fn valid_key(&self, _: &str) -> bool {
+        true
+    }
+
+    pub(crate) fn check_len(&self, len: usize) -> Result<(), Error> {
+        match self.max_keys {
+            Some(max) if len > max => Err(Error::KeyCount),
+            _ => Ok(()),
+        }
+    }
+
+    fn valid_field(
+        &self,
+        mut name: VecDeque<NamePart>,
+        path: &mut Vec<NamePart>,
+    ) -> Option<FieldTerminator> {
+        trace!("Checking {:?} and {:?}", self, name);
+        let key = match name.pop_front() {
+            Some(NamePart::Map(key)) => key,
+            Some(NamePart::Index(index)) => index.to_string(),
+            _ => return None,
+        };
+
+        if !self.valid_key(&key) {
+            return None;
         }
+
+        path.push(NamePart::Map(key));
+        self.inner.valid_field(name, path)
+    }
+}
+
+impl From<Dict> for Field {
+    fn from(dict: Dict) -> Self {
+        dict.finalize()
     }
 }
 
@@ -441,10 +1709,10 @@ impl Map {
 /// # extern crate mime;
 /// # extern crate form_data;
 /// # use std::path::{Path, PathBuf};
-/// # use form_data::{Form, Field, FilenameGenerator};
+/// # use form_data::{Form, Field, FilenameGenerator, UploadContext};
 /// # struct Gen;
 /// # impl FilenameGenerator for Gen {
-/// #     fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
+/// #     fn next_filename(&self, _: &UploadContext) -> Option<PathBuf> {
 /// #         Some(AsRef::<Path>::as_ref("path.png").to_owned())
 /// #     }
 /// # }
@@ -476,6 +1744,12 @@ pub struct Form {
     pub max_files: u32,
     pub max_file_size: usize,
     inner: Map,
+    validators: Vec<Arc<Validator>>,
+    name_parser: Arc<NameParser>,
+    duplicates: DuplicateFields,
+    default_charset: Option<String>,
+    sanitizer: Sanitizer,
+    storage_root: Option<PathBuf>,
 }
 
 impl Form {
@@ -489,6 +1763,12 @@ impl Form {
             max_files: 20,
             max_file_size: 10_000_000,
             inner: Map::new(),
+            validators: Vec::new(),
+            name_parser: Arc::new(Brackets),
+            duplicates: DuplicateFields::KeepFirst,
+            default_charset: None,
+            sanitizer: Sanitizer::new(),
+            storage_root: None,
         }
     }
 
@@ -528,14 +1808,268 @@ impl Form {
         self
     }
 
-    pub fn field(mut self, name: &str, field: Field) -> Self {
+    /// Set how fields that aren't defined in the form are handled
+    ///
+    /// By default, unknown fields fail the upload. Nested maps have their own policy, see
+    /// `Map::unknown_fields`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, Form, UnknownFields};
+    /// #
+    /// let form = Form::new()
+    ///     .field("title", Field::text())
+    ///     .unknown_fields(UnknownFields::Skip);
+    /// ```
+    pub fn unknown_fields(mut self, policy: UnknownFields) -> Self {
+        self.inner = self.inner.unknown_fields(policy);
+
+        self
+    }
+
+    /// Set how field names are split into their parts
+    ///
+    /// By default, names are parsed PHP-style with `Brackets`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Dotted, Field, Form};
+    /// #
+    /// let form = Form::new()
+    ///     .field("user", Field::map().field("name", Field::text()))
+    ///     .name_parser(Dotted);
+    /// ```
+    pub fn name_parser<P>(mut self, parser: P) -> Self
+    where
+        P: NameParser + 'static,
+    {
+        self.name_parser = Arc::new(parser);
+
+        self
+    }
+
+    /// Set how a name that's submitted more than once is handled
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{DuplicateFields, Field, Form};
+    /// #
+    /// let form = Form::new()
+    ///     .field("title", Field::text())
+    ///     .duplicate_fields(DuplicateFields::Reject);
+    /// ```
+    pub fn duplicate_fields(mut self, policy: DuplicateFields) -> Self {
+        self.duplicates = policy;
+
+        self
+    }
+
+    /// Set the charset used to decode multipart text fields that don't specify one
+    ///
+    /// A part's own `charset` parameter is used first, followed by the value of a `_charset_`
+    /// field as described by RFC 7578, and then this default. The `_charset_` field itself is
+    /// never included in the result. Without a default, text is decoded as UTF-8.
+    ///
+    /// Charsets other than UTF-8, ISO-8859-1, and US-ASCII require the `encoding_rs` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, Form};
+    /// #
+    /// let form = Form::new()
+    ///     .field("name", Field::text())
+    ///     .default_charset("Shift_JIS");
+    /// ```
+    pub fn default_charset(mut self, charset: &str) -> Self {
+        self.default_charset = Some(charset.to_owned());
+
+        self
+    }
+
+    /// Set the rules used to clean up the filenames of uploaded files
+    ///
+    /// Files are always sanitized, by default with `Sanitizer::new()`. Uploads whose names
+    /// can't be salvaged are rejected.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{AllowedChars, Form, Sanitizer};
+    /// #
+    /// let form = Form::new().sanitize_filenames(
+    ///     Sanitizer::new()
+    ///         .max_len(100)
+    ///         .allowed_chars(AllowedChars::Ascii),
+    /// );
+    /// ```
+    pub fn sanitize_filenames(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = sanitizer;
+
+        self
+    }
+
+    /// Keep every uploaded file inside a directory
+    ///
+    /// Paths from a `FilenameGenerator` are resolved relative to the root, which is created if
+    /// it doesn't exist. Absolute paths are allowed as long as they're already inside the root.
+    /// Uploads are rejected before anything is written if a generated path contains `..`,
+    /// points outside the root, or leads out of it through a symbolic link.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::Form;
+    /// #
+    /// let form = Form::new().storage_root("/var/lib/uploads");
+    /// ```
+    pub fn storage_root<P>(mut self, root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.storage_root = Some(root.as_ref().to_owned());
+
+        self
+    }
+
+    pub fn field<F>(mut self, name: &str, field: F) -> Self
+    where
+        F: Into<Field>,
+    {
         self.inner = self.inner.field(name, field);
 
         self
     }
 
-    pub(crate) fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator> {
-        self.inner.valid_field(name.clone())
+    /// Add a validation step that runs once the whole form has been parsed
+    ///
+    /// Validators receive the fully parsed `Value`, and can check rules involving multiple fields
+    /// or consult external state before passing the value along. Validators run in the order
+    /// they were added. If a validator fails, any files stored while parsing the form are
+    /// removed before the error is returned. Use `Error::validation` to reject a form with a
+    /// custom error response.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use actix_web::{HttpResponse, ResponseError};
+    /// # use form_data::{Error, Field, Form, Value};
+    /// #[derive(Debug)]
+    /// struct Mismatch;
+    ///
+    /// impl std::fmt::Display for Mismatch {
+    ///     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    ///         write!(f, "Every image needs a caption")
+    ///     }
+    /// }
+    ///
+    /// impl ResponseError for Mismatch {
+    ///     fn error_response(&self) -> HttpResponse {
+    ///         HttpResponse::UnprocessableEntity().finish()
+    ///     }
+    /// }
+    ///
+    /// let form = Form::new()
+    ///     .field("images", Field::array(Field::bytes()))
+    ///     .field("captions", Field::array(Field::text()))
+    ///     .validate(|value: Value| {
+    ///         let len = |key: &str| match value {
+    ///             Value::Map(ref hm) => match hm.get(key) {
+    ///                 Some(Value::Array(ref v)) => v.len(),
+    ///                 _ => 0,
+    ///             },
+    ///             _ => 0,
+    ///         };
+    ///
+    ///         if len("images") == len("captions") {
+    ///             Ok(value)
+    ///         } else {
+    ///             Err(Error::validation(Mismatch))
+    ///         }
+    ///     });
+    /// ```
+    pub fn validate<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(Value) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = Value, Error = Error>,
+        R::Future: 'static,
+    {
+        self.validators.push(Arc::new(move |value: Value| {
+            Box::new(f(value).into_future()) as Box<Future<Item = Value, Error = Error>>
+        }));
+
+        self
+    }
+
+    pub(crate) fn validators(&self) -> &[Arc<Validator>] {
+        &self.validators
+    }
+
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn fields(&self) -> &Map {
+        &self.inner
+    }
+
+    /// The largest body that can hold `max_fields` fields of `max_field_size` bytes
+    pub(crate) fn max_body_size(&self) -> usize {
+        (self.max_fields as usize).saturating_mul(self.max_field_size)
+    }
+
+    pub(crate) fn charset(&self) -> Option<&str> {
+        self.default_charset.as_deref()
+    }
+
+    pub(crate) fn sanitizer(&self) -> &Sanitizer {
+        &self.sanitizer
+    }
+
+    pub(crate) fn root(&self) -> Option<&Path> {
+        self.storage_root.as_deref()
+    }
+
+    pub(crate) fn duplicates(&self) -> DuplicateFields {
+        self.duplicates
+    }
+
+    pub(crate) fn parse_name(&self, name: &str) -> Result<Vec<NamePart>, Error> {
+        self.name_parser.parse(name)
+    }
+
+    /// Find the field a name refers to, along with the name's parts as the schema reads them
+    pub(crate) fn valid_field(
+        &self,
+        name: Vec<NamePart>,
+    ) -> Option<(Vec<NamePart>, FieldTerminator)> {
+        let mut path = Vec::with_capacity(name.len());
+
+        if let Some(term) = self
+            .inner
+            .valid_field(name.iter().cloned().collect(), &mut path)
+        {
+            return Some((path, term));
+        }
+
+        if !self.name_parser.implicit_arrays() {
+            return None;
+        }
+
+        // The name may stop at an array, in which case it refers to a new element
+        let mut name: VecDeque<NamePart> = name.into_iter().collect();
+        name.push_back(NamePart::Array);
+        path.clear();
+
+        let term = self.inner.valid_field(name, &mut path)?;
+
+        Some((path, term))
+    }
+
+    pub(crate) fn fill_defaults(&self, value: &mut Value) {
+        if let Value::Map(ref mut hm) = *value {
+            self.inner.fill_defaults(hm);
+        }
+    }
+
+    pub(crate) fn check_keys(&self, value: &Value) -> Result<(), Error> {
+        match *value {
+            Value::Map(ref hm) => self.inner.check_keys(hm),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -560,10 +2094,15 @@ impl ContentDisposition {
     }
 }
 
+/// One segment of a field's name, as produced by a `NameParser`
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum NamePart {
+pub enum NamePart {
+    /// A key in a map, such as `name` in `user[name]`
     Map(String),
+    /// A new element of an array, such as the `[]` in `tags[]`
     Array,
+    /// A positioned element of an array, such as the `[0]` in `items[0]`
+    Index(usize),
 }
 
 impl NamePart {
@@ -577,11 +2116,26 @@ impl NamePart {
 
 #[derive(Clone)]
 pub(crate) enum FieldTerminator {
-    File(Arc<FilenameGenerator>),
+    File(Arc<AsyncFilenameGenerator>),
     Bytes,
-    Int,
-    Float,
-    Text,
+    Int(Int),
+    Float(Float),
+    Text(Text),
+    Bool(Bool),
+    Choice(Choice),
+    #[cfg(feature = "chrono")]
+    Date(Date),
+    #[cfg(feature = "chrono")]
+    Time(Time),
+    #[cfg(feature = "chrono")]
+    DateTime(DateTime),
+    #[cfg(feature = "chrono")]
+    Timestamp(Timestamp),
+    #[cfg(feature = "serde_json")]
+    Json(Json),
+    Custom(Custom),
+    Skip,
+    Collect,
 }
 
 impl fmt::Debug for FieldTerminator {
@@ -589,13 +2143,62 @@ impl fmt::Debug for FieldTerminator {
         match *self {
             FieldTerminator::File(_) => write!(f, "File(filename_generator)"),
             FieldTerminator::Bytes => write!(f, "Bytes"),
-            FieldTerminator::Int => write!(f, "Int"),
-            FieldTerminator::Float => write!(f, "Float"),
-            FieldTerminator::Text => write!(f, "Text"),
+            FieldTerminator::Int(ref i) => write!(f, "Int({:?})", i),
+            FieldTerminator::Float(ref fl) => write!(f, "Float({:?})", fl),
+            FieldTerminator::Text(ref t) => write!(f, "Text({:?})", t),
+            FieldTerminator::Bool(ref b) => write!(f, "Bool({:?})", b),
+            FieldTerminator::Choice(ref c) => write!(f, "Choice({:?})", c),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Date(ref d) => write!(f, "Date({:?})", d),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Time(ref t) => write!(f, "Time({:?})", t),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::DateTime(ref dt) => write!(f, "DateTime({:?})", dt),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Timestamp(ref ts) => write!(f, "Timestamp({:?})", ts),
+            #[cfg(feature = "serde_json")]
+            FieldTerminator::Json(ref json) => write!(f, "Json({:?})", json),
+            FieldTerminator::Custom(ref c) => write!(f, "{:?}", c),
+            FieldTerminator::Skip => write!(f, "Skip"),
+            FieldTerminator::Collect => write!(f, "Collect"),
+        }
+    }
+}
+
+impl FieldTerminator {
+    pub(crate) fn parse_text(&self, name: &str, string: String) -> Result<MultipartContent, Error> {
+        match *self {
+            FieldTerminator::File(_) => Err(Error::FieldType),
+            FieldTerminator::Bytes => Ok(MultipartContent::Bytes(string.into())),
+            FieldTerminator::Float(ref f) => f.parse(name, &string).map(MultipartContent::Float),
+            FieldTerminator::Int(ref i) => i.parse(name, &string).map(MultipartContent::Int),
+            FieldTerminator::Text(ref t) => t.parse(name, string).map(MultipartContent::Text),
+            FieldTerminator::Bool(ref b) => b
+                .parse(&string)
+                .map(MultipartContent::Bool)
+                .ok_or(Error::ParseBool),
+            FieldTerminator::Choice(ref c) => c.parse(&string),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Date(ref d) => d.parse(&string).map(MultipartContent::Date),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Time(ref t) => t.parse(&string).map(MultipartContent::Time),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::DateTime(ref dt) => dt.parse(&string).map(MultipartContent::DateTime),
+            #[cfg(feature = "chrono")]
+            FieldTerminator::Timestamp(ref ts) => {
+                ts.parse(&string).map(MultipartContent::Timestamp)
+            }
+            #[cfg(feature = "serde_json")]
+            FieldTerminator::Json(ref json) => json.parse(name, string.as_bytes()),
+            FieldTerminator::Custom(ref c) => c.parse(name, string.as_bytes()),
+            FieldTerminator::Skip => Err(Error::FieldType),
+            FieldTerminator::Collect => Ok(MultipartContent::Text(string)),
         }
     }
 }
 
+pub(crate) type Validator = Fn(Value) -> Box<Future<Item = Value, Error = Error>> + Send + Sync;
+
 pub(crate) type MultipartHash = (Vec<NamePart>, MultipartContent);
 pub(crate) type MultipartForm = Vec<MultipartHash>;
 
@@ -604,9 +2207,24 @@ pub(crate) enum MultipartContent {
     File {
         filename: String,
         stored_as: PathBuf,
+        size: u64,
+        /// Whether the file was already on disk, from an upload with the same contents
+        deduplicated: bool,
     },
     Bytes(Bytes),
     Text(String),
     Int(i64),
     Float(f64),
+    Bool(bool),
+    #[cfg(feature = "chrono")]
+    Date(chrono::NaiveDate),
+    #[cfg(feature = "chrono")]
+    Time(chrono::NaiveTime),
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::NaiveDateTime),
+    #[cfg(feature = "chrono")]
+    Timestamp(chrono::DateTime<chrono::Utc>),
+    #[cfg(feature = "serde_json")]
+    Json(Value),
+    Custom(CustomValue),
 }
@@ -17,91 +17,322 @@
  * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+#[cfg(feature = "sha2")]
+use std::path::Path;
 use std::{
-    collections::HashMap,
-    fs::DirBuilder,
-    path::{Path, PathBuf},
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap, HashMap, HashSet},
+    fs::{self, DirBuilder},
+    mem,
+    path::PathBuf,
+    rc::Rc,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
 };
 
-use bytes::BytesMut;
+use actix_multipart::MultipartError;
+use actix_threadpool::BlockingError;
+use actix_web::{error::PayloadError, HttpRequest};
+use bytes::{Bytes, BytesMut};
 use futures::{
-    future::{result, Either},
+    future::{ok, result, Either},
+    sync::oneshot,
     Future, Stream,
 };
-use log::trace;
+use http::header::CONTENT_DISPOSITION;
+use log::{trace, warn};
+#[cfg(feature = "sha2")]
+use sha2::{Digest, Sha256};
 
 use crate::{
+    charset,
+    context::UploadContext,
+    disposition,
     error::Error,
+    storage,
+    transfer::Encoding,
     types::{
-        self, ContentDisposition, MultipartContent, MultipartForm, MultipartHash, NamePart, Value,
+        self, ContentDisposition, DuplicateFields, MultipartContent, MultipartForm, MultipartHash,
+        NamePart, Value,
     },
-    FilenameGenerator,
+    AsyncFilenameGenerator,
 };
 
-fn consolidate(mf: MultipartForm) -> Value {
-    mf.into_iter().fold(
-        Value::Map(HashMap::new()),
-        |mut acc, (mut nameparts, content)| {
-            let start_value = Value::from(content);
+/// An intermediate tree used to assemble the submitted parts into a `Value`
+///
+/// Maps remember the order their keys were first submitted in, which is kept in the resulting
+/// `Value`. Arrays keep explicitly indexed entries
+/// separate from appended ones, so `items[3]` and `items[0]` end up in order no matter which
+/// was sent first, and a large index doesn't allocate empty slots.
+///
+/// Appended elements that continue into a nested name, like `items[][name]`, are grouped the
+/// way PHP and Rails do it: the part is added to the last element unless that element already
+/// holds a value at the same place, in which case a new element is started. This turns
+/// `items[][name]`, `items[][qty]`, `items[][name]`, `items[][qty]` into two records.
+enum Node {
+    Map(HashMap<String, usize>, Vec<(String, Node)>),
+    Array(BTreeMap<usize, Node>, Vec<Node>),
+    Leaf(Value),
+}
 
-            nameparts.reverse();
-            let value = nameparts
-                .into_iter()
-                .fold(start_value, |acc, namepart| match namepart {
-                    NamePart::Map(name) => {
-                        let mut hm = HashMap::new();
+/// Why a value couldn't be added to a `Node`
+enum Conflict {
+    /// The name was already submitted, and the policy rejects duplicates
+    Duplicate,
+    /// The name treats an earlier value as a different kind of value, like `a` and `a[b]`
+    Shape,
+}
 
-                        hm.insert(name, acc);
+impl Node {
+    fn new(path: &[NamePart], value: Value) -> Self {
+        match path.split_first() {
+            Some((NamePart::Map(key), rest)) => {
+                let mut keys = HashMap::new();
+                keys.insert(key.to_owned(), 0);
 
-                        Value::Map(hm)
-                    }
-                    NamePart::Array => Value::Array(vec![acc]),
-                });
+                Node::Map(keys, vec![(key.to_owned(), Node::new(rest, value))])
+            }
+            Some((NamePart::Index(index), rest)) => {
+                let mut indexed = BTreeMap::new();
+                indexed.insert(*index, Node::new(rest, value));
 
-            acc.merge(value);
-            acc
-        },
-    )
-}
+                Node::Array(indexed, Vec::new())
+            }
+            Some((NamePart::Array, rest)) => {
+                Node::Array(BTreeMap::new(), vec![Node::new(rest, value)])
+            }
+            None => Node::Leaf(value),
+        }
+    }
 
-fn parse_multipart_name(name: String) -> Result<Vec<NamePart>, Error> {
-    name.split('[')
-        .map(|part| {
-            if part.len() == 1 && part.ends_with(']') {
-                NamePart::Array
-            } else if part.ends_with(']') {
-                NamePart::Map(part.trim_end_matches(']').to_owned())
-            } else {
-                NamePart::Map(part.to_owned())
+    /// Insert a value, adding the files of any value the policy throws away to `discarded`
+    fn insert(
+        &mut self,
+        path: &[NamePart],
+        value: Value,
+        duplicates: DuplicateFields,
+        discarded: &mut Vec<PathBuf>,
+    ) -> Result<(), Conflict> {
+        let (part, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return self.duplicate(value, duplicates, discarded),
+        };
+
+        match (self, part) {
+            (Node::Map(keys, entries), NamePart::Map(key)) => match keys.get(key) {
+                Some(&i) => entries[i].1.insert(rest, value, duplicates, discarded),
+                None => {
+                    keys.insert(key.to_owned(), entries.len());
+                    entries.push((key.to_owned(), Node::new(rest, value)));
+                    Ok(())
+                }
+            },
+            (Node::Array(indexed, _), NamePart::Index(index)) => match indexed.entry(*index) {
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().insert(rest, value, duplicates, discarded)
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Node::new(rest, value));
+                    Ok(())
+                }
+            },
+            (Node::Array(_, appended), NamePart::Array) => match appended.last_mut() {
+                Some(last) if !rest.is_empty() && !last.occupied(rest) => {
+                    last.insert(rest, value, duplicates, discarded)
+                }
+                _ => {
+                    appended.push(Node::new(rest, value));
+                    Ok(())
+                }
+            },
+            _ => Err(Conflict::Shape),
+        }
+    }
+
+    fn duplicate(
+        &mut self,
+        value: Value,
+        duplicates: DuplicateFields,
+        discarded: &mut Vec<PathBuf>,
+    ) -> Result<(), Conflict> {
+        match duplicates {
+            DuplicateFields::Reject => return Err(Conflict::Duplicate),
+            DuplicateFields::KeepFirst => value.stored_files(discarded),
+            DuplicateFields::KeepLast => {
+                mem::replace(self, Node::Leaf(value)).stored_files(discarded)
             }
-        })
-        .fold(Ok(vec![]), |acc, part| match acc {
-            Ok(mut v) => {
-                if v.len() == 0 && !part.is_map() {
-                    return Err(Error::ContentDisposition);
+            DuplicateFields::Array => match *self {
+                Node::Array(_, ref mut appended) => appended.push(Node::Leaf(value)),
+                ref mut node => {
+                    let first = mem::replace(node, Node::Array(BTreeMap::new(), Vec::new()));
+                    *node = Node::Array(BTreeMap::new(), vec![first, Node::Leaf(value)]);
                 }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Whether inserting at `path` would collide with a value that's already present
+    fn occupied(&self, path: &[NamePart]) -> bool {
+        let (part, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return true,
+        };
 
-                v.push(part);
-                Ok(v)
+        match (self, part) {
+            (Node::Map(keys, entries), NamePart::Map(key)) => keys
+                .get(key)
+                .map(|&i| entries[i].1.occupied(rest))
+                .unwrap_or(false),
+            (Node::Array(indexed, _), NamePart::Index(index)) => indexed
+                .get(index)
+                .map(|node| node.occupied(rest))
+                .unwrap_or(false),
+            (Node::Array(_, appended), NamePart::Array) => match appended.last() {
+                Some(last) if !rest.is_empty() => last.occupied(rest),
+                _ => false,
+            },
+            _ => true,
+        }
+    }
+
+    fn stored_files(&self, files: &mut Vec<PathBuf>) {
+        match self {
+            Node::Map(_, entries) => {
+                for (_, node) in entries {
+                    node.stored_files(files);
+                }
+            }
+            Node::Array(indexed, appended) => {
+                for node in indexed.values().chain(appended) {
+                    node.stored_files(files);
+                }
             }
-            Err(e) => Err(e),
+            Node::Leaf(value) => value.stored_files(files),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Node::Map(_, entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, node)| (key, node.into_value()))
+                    .collect(),
+            ),
+            Node::Array(indexed, appended) => Value::Array(
+                indexed
+                    .into_values()
+                    .chain(appended)
+                    .map(Node::into_value)
+                    .collect(),
+            ),
+            Node::Leaf(value) => value,
+        }
+    }
+}
+
+fn display_name(nameparts: &[NamePart]) -> String {
+    nameparts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| match *part {
+            NamePart::Map(ref key) if i == 0 => key.to_owned(),
+            NamePart::Map(ref key) => format!("[{}]", key),
+            NamePart::Array => "[]".to_owned(),
+            NamePart::Index(index) => format!("[{}]", index),
         })
+        .collect()
+}
+
+/// Assemble the parts into a `Value`, along with the files of parts the duplicate policy threw
+/// away
+///
+/// Only files in `created` are reported, and never one the `Value` still refers to, since an
+/// identical upload can share a content-hashed file.
+fn consolidate(
+    mf: MultipartForm,
+    duplicates: DuplicateFields,
+    created: &HashSet<PathBuf>,
+) -> Result<(Value, Vec<PathBuf>), Error> {
+    let mut root = Node::Map(HashMap::new(), Vec::new());
+    let mut discarded = Vec::new();
+
+    for (nameparts, content) in mf {
+        root.insert(&nameparts, Value::from(content), duplicates, &mut discarded)
+            .map_err(|conflict| match conflict {
+                Conflict::Duplicate => Error::DuplicateField(display_name(&nameparts)),
+                Conflict::Shape => Error::FieldShape(display_name(&nameparts)),
+            })?;
+    }
+
+    let value = root.into_value();
+
+    let mut kept = Vec::new();
+    value.stored_files(&mut kept);
+
+    discarded.retain(|file| created.contains(file) && !kept.contains(file));
+    discarded.sort();
+    discarded.dedup();
+
+    Ok((value, discarded))
+}
+
+/// The files a request wrote itself, which are the only ones it may remove
+///
+/// Content-hashed files that were already on disk are left out, since earlier uploads may still
+/// refer to them.
+fn created_files(mf: &MultipartForm) -> HashSet<PathBuf> {
+    mf.iter()
+        .filter_map(|(_, content)| match content {
+            MultipartContent::File {
+                stored_as,
+                deduplicated: false,
+                ..
+            } => Some(stored_as.clone()),
+            _ => None,
+        })
+        .collect()
 }
 
 fn parse_content_disposition(field: &actix_multipart::Field) -> ContentDisposition {
-    match field.content_disposition() {
-        Some(x) => ContentDisposition {
-            name: x.get_name().map(|v| v.to_string()),
-            filename: x.get_filename().map(|v| v.to_string()),
-        },
+    match field.headers().get(CONTENT_DISPOSITION) {
+        Some(header) => disposition::parse(header.as_bytes()),
         None => ContentDisposition::empty(),
     }
 }
 
+/// The part's body, with its `Content-Transfer-Encoding` decoded
+///
+/// The encoded body is limited to what could decode to `max` bytes, since padding like
+/// whitespace or soft line breaks decodes to nothing and would otherwise never be counted.
+fn decoded_field(
+    field: actix_multipart::Field,
+    max: usize,
+    is_file: bool,
+) -> Result<Box<Stream<Item = Bytes, Error = Error>>, Error> {
+    let encoding = Encoding::from_headers(field.headers())?;
+    let limit = encoding.raw_limit(max);
+    let mut size = 0;
+
+    let raw = field.map_err(Error::Multipart).and_then(move |bytes| {
+        size += bytes.len();
+
+        if size <= limit {
+            Ok(bytes)
+        } else if is_file {
+            Err(Error::FileSize)
+        } else {
+            Err(Error::FieldSize)
+        }
+    });
+
+    Ok(encoding.decode(raw))
+}
+
 #[cfg(unix)]
 fn build_dir(stored_dir: PathBuf) -> Result<(), Error> {
     use std::os::unix::fs::DirBuilderExt;
@@ -121,194 +352,703 @@ fn build_dir(stored_dir: PathBuf) -> Result<(), Error> {
         .map_err(|_| Error::MkDir)
 }
 
+/// Hashes files as they're written, for generators that name files after their contents
+#[cfg(feature = "sha2")]
+#[derive(Clone)]
+struct ContentHasher(Option<Rc<RefCell<Sha256>>>);
+
+#[cfg(feature = "sha2")]
+impl ContentHasher {
+    fn new(gen: &AsyncFilenameGenerator) -> Self {
+        if gen.hashes_content() {
+            ContentHasher(Some(Rc::new(RefCell::new(Sha256::new()))))
+        } else {
+            ContentHasher(None)
+        }
+    }
+
+    fn update(&self, bytes: &[u8]) {
+        if let Some(ref hasher) = self.0 {
+            hasher.borrow_mut().input(bytes);
+        }
+    }
+
+    /// Move the file to the name the generator picks for its digest, once it's fully written
+    ///
+    /// The temporary file is removed if it can't be moved, or if a file with the same name, and
+    /// so the same contents, is already there.
+    fn finish(
+        self,
+        gen: Arc<AsyncFilenameGenerator>,
+        done: oneshot::Receiver<()>,
+        content: MultipartContent,
+    ) -> Box<Future<Item = MultipartContent, Error = Error>> {
+        let digest = match self.0 {
+            Some(hasher) => hasher.borrow().clone().result(),
+            None => return Box::new(ok(content)),
+        };
+
+        let (filename, stored_as, size) = match content {
+            MultipartContent::File {
+                filename,
+                stored_as,
+                size,
+                ..
+            } => (filename, stored_as, size),
+            content => return Box::new(ok(content)),
+        };
+
+        Box::new(
+            done.then(move |written| {
+                actix_threadpool::run(move || {
+                    let moved = written
+                        .map_err(|_| Error::WriteFile)
+                        .and_then(|_| hashed_path(&*gen, &stored_as, &digest))
+                        .and_then(|renamed| {
+                            move_hashed(&stored_as, &renamed).map(|existing| (renamed, existing))
+                        });
+
+                    if moved.is_err() {
+                        remove_temporary(&stored_as);
+                    }
+
+                    moved
+                })
+                .map_err(|e| match e {
+                    BlockingError::Error(e) => e,
+                    BlockingError::Canceled => Error::WriteFile,
+                })
+            })
+            .map(move |(stored_as, deduplicated)| MultipartContent::File {
+                filename,
+                stored_as,
+                size,
+                deduplicated,
+            }),
+        )
+    }
+
+    /// Remove the temporary file once the writer is done with it, after the upload failed
+    fn discard(
+        self,
+        done: oneshot::Receiver<()>,
+        stored_as: PathBuf,
+        e: Error,
+    ) -> Box<Future<Item = MultipartContent, Error = Error>> {
+        if self.0.is_none() {
+            return Box::new(result(Err(e)));
+        }
+
+        Box::new(done.then(move |_| {
+            actix_threadpool::run(move || {
+                remove_temporary(&stored_as);
+                Ok(()) as Result<(), ()>
+            })
+            .then(move |_| Err(e))
+        }))
+    }
+}
+
+/// The path a generator picks for a file's digest, which has to be next to the temporary file
+#[cfg(feature = "sha2")]
+fn hashed_path(
+    gen: &AsyncFilenameGenerator,
+    stored_as: &Path,
+    digest: &[u8],
+) -> Result<PathBuf, Error> {
+    let renamed = gen
+        .hashed_filename(stored_as, digest)
+        .ok_or(Error::GenFilename)?;
+
+    // The file can't be moved anywhere a storage root wouldn't allow
+    if renamed.parent() != stored_as.parent() || renamed.file_name().is_none() {
+        return Err(Error::StorageRoot);
+    }
+
+    Ok(renamed)
+}
+
+/// Move a hashed file into place without replacing a file that's already there, returning
+/// whether there was one
+#[cfg(feature = "sha2")]
+fn move_hashed(temporary: &Path, renamed: &Path) -> Result<bool, Error> {
+    match fs::symlink_metadata(renamed) {
+        Ok(ref metadata) if metadata.file_type().is_file() => {
+            trace!("Keeping existing file {:?}", renamed);
+            fs::remove_file(temporary).map_err(Error::FsPool)?;
+            Ok(true)
+        }
+        Ok(_) => Err(Error::WriteFile),
+        Err(_) => {
+            fs::rename(temporary, renamed).map_err(Error::FsPool)?;
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(feature = "sha2")]
+fn remove_temporary(temporary: &Path) {
+    if let Err(e) = fs::remove_file(temporary) {
+        warn!("Failed to remove {:?}, {}", temporary, e);
+    }
+}
+
+#[cfg(not(feature = "sha2"))]
+#[derive(Clone)]
+struct ContentHasher;
+
+#[cfg(not(feature = "sha2"))]
+impl ContentHasher {
+    fn new(_: &AsyncFilenameGenerator) -> Self {
+        ContentHasher
+    }
+
+    fn update(&self, _: &[u8]) {}
+
+    fn finish(
+        self,
+        _: Arc<AsyncFilenameGenerator>,
+        _: oneshot::Receiver<()>,
+        content: MultipartContent,
+    ) -> Box<Future<Item = MultipartContent, Error = Error>> {
+        Box::new(ok(content))
+    }
+
+    fn discard(
+        self,
+        _: oneshot::Receiver<()>,
+        _: PathBuf,
+        e: Error,
+    ) -> Box<Future<Item = MultipartContent, Error = Error>> {
+        Box::new(result(Err(e)))
+    }
+}
+
 fn handle_file_upload(
     field: actix_multipart::Field,
-    gen: Arc<FilenameGenerator>,
+    gen: Arc<AsyncFilenameGenerator>,
+    path: &[NamePart],
     filename: Option<String>,
+    request: Option<&HttpRequest>,
     form: types::Form,
 ) -> Box<Future<Item = MultipartContent, Error = Error>> {
-    let filename = match filename {
+    let original_filename = match filename {
+        Some(filename) => filename,
+        None => return Box::new(result(Err(Error::Filename))),
+    };
+
+    let filename = match form.sanitizer().sanitize(&original_filename) {
         Some(filename) => filename,
         None => return Box::new(result(Err(Error::Filename))),
     };
 
-    let path: &Path = filename.as_ref();
-    let filename = path.file_name().and_then(|filename| filename.to_str());
+    let generated = gen.generate(&UploadContext::new(
+        path,
+        &filename,
+        &original_filename,
+        field.content_type(),
+        field.headers(),
+        request,
+    ));
 
-    let filename = if let Some(filename) = filename {
-        filename.to_owned()
-    } else {
-        return Box::new(result(Err(Error::Filename)));
+    let field = match decoded_field(field, form.max_file_size, true) {
+        Ok(field) => field,
+        Err(e) => return Box::new(result(Err(e))),
     };
 
-    let stored_as = match gen.next_filename(field.content_type()) {
-        Some(file_path) => file_path,
-        None => return Box::new(result(Err(Error::GenFilename))),
+    Box::new(generated.and_then(move |stored_as| store_file(field, gen, filename, stored_as, form)))
+}
+
+/// Write a file's contents to the path its generator picked
+fn store_file(
+    field: Box<Stream<Item = Bytes, Error = Error>>,
+    gen: Arc<AsyncFilenameGenerator>,
+    filename: String,
+    stored_as: PathBuf,
+    form: types::Form,
+) -> Box<Future<Item = MultipartContent, Error = Error>> {
+    let root = form.root().map(ToOwned::to_owned);
+
+    let stored_as = match root {
+        Some(ref root) => match storage::resolve(root, &stored_as) {
+            Ok(stored_as) => stored_as,
+            Err(e) => return Box::new(result(Err(e))),
+        },
+        None => stored_as,
     };
 
     let mut stored_dir = stored_as.clone();
     stored_dir.pop();
 
-    let mkdir_fut = actix_threadpool::run(move || build_dir(stored_dir.clone()));
+    let checked = stored_as.clone();
+    let mkdir_fut = actix_threadpool::run(move || match root {
+        Some(root) => {
+            build_dir(root.clone())?;
+            storage::check_links(&root, &checked)?;
+            build_dir(stored_dir.clone())?;
+            storage::check_links(&root, &checked)
+        }
+        None => build_dir(stored_dir.clone()),
+    })
+    .map_err(|e| match e {
+        BlockingError::Error(e) => e,
+        BlockingError::Canceled => Error::MkDir,
+    });
 
     let counter = Arc::new(AtomicUsize::new(0));
+    let written = Arc::clone(&counter);
+
+    let hasher = ContentHasher::new(&*gen);
+
+    Box::new(mkdir_fut.and_then(move |_| {
+        let (write, done) = crate::file_future::write(stored_as.clone());
+        let hashed = hasher.clone();
 
-    Box::new(mkdir_fut.map_err(|_| Error::MkDir).and_then(move |_| {
-        let write = crate::file_future::write(stored_as.clone());
         field
-            .map_err(Error::Multipart)
             .and_then(move |bytes| {
                 let size = counter.fetch_add(bytes.len(), Ordering::Relaxed) + bytes.len();
 
                 if size > form.max_file_size {
                     Err(Error::FileSize)
                 } else {
+                    hashed.update(&bytes);
                     Ok(bytes)
                 }
             })
             .forward(write)
-            .map(move |_| MultipartContent::File {
-                filename,
-                stored_as,
+            .then(move |forwarded| match forwarded {
+                Ok(_) => hasher.finish(
+                    gen,
+                    done,
+                    MultipartContent::File {
+                        filename,
+                        stored_as,
+                        size: written.load(Ordering::Relaxed) as u64,
+                        deduplicated: false,
+                    },
+                ),
+                Err(e) => hasher.discard(done, stored_as, e),
             })
     }))
 }
 
+fn buffer_field(
+    field: actix_multipart::Field,
+    max: usize,
+) -> impl Future<Item = BytesMut, Error = Error> {
+    result(decoded_field(field, max, false)).and_then(move |field| {
+        field.fold(BytesMut::new(), move |mut acc, bytes| {
+            if acc.len() + bytes.len() < max {
+                acc.extend(bytes);
+                Ok(acc)
+            } else {
+                Err(Error::FieldSize)
+            }
+        })
+    })
+}
+
+fn read_charset(
+    field: actix_multipart::Field,
+    form_charset: Rc<RefCell<Option<String>>>,
+    form: types::Form,
+) -> Box<Future<Item = (), Error = Error>> {
+    Box::new(buffer_field(field, form.max_field_size).map(move |bytes| {
+        let label = String::from_utf8_lossy(&bytes).trim().to_owned();
+        trace!("Using charset {} for following fields", label);
+        *form_charset.borrow_mut() = Some(label);
+    }))
+}
+
 fn handle_form_data(
     field: actix_multipart::Field,
     term: types::FieldTerminator,
+    name: String,
+    form_charset: Rc<RefCell<Option<String>>>,
     form: types::Form,
 ) -> Box<Future<Item = MultipartContent, Error = Error>> {
     trace!("In handle_form_data, term: {:?}", term);
-    let term2 = term.clone();
+
+    // A charset on the part itself wins over the _charset_ field, which wins over the form's
+    // default
+    let part_charset = field
+        .content_type()
+        .get_param(mime::CHARSET)
+        .map(|param| param.as_str().to_owned());
+    let default_charset = form.charset().map(ToOwned::to_owned);
+
+    Box::new(
+        buffer_field(field, form.max_field_size).and_then(move |bytes| {
+            let label = part_charset
+                .or_else(|| form_charset.borrow().clone())
+                .or(default_charset);
+            let label = label.as_deref();
+
+            match term {
+                types::FieldTerminator::Bytes => Ok(MultipartContent::Bytes(bytes.freeze())),
+                types::FieldTerminator::Custom(custom) => custom.parse(&name, &bytes),
+                types::FieldTerminator::Collect => charset::decode(label, &bytes)
+                    .map(MultipartContent::Text)
+                    .or_else(|_| Ok(MultipartContent::Bytes(bytes.freeze()))),
+                term => charset::decode(label, &bytes).and_then(|string| {
+                    trace!("Matching: {:?}", string);
+                    term.parse_text(&name, string)
+                }),
+            }
+        }),
+    )
+}
+
+fn skip_field(
+    field: actix_multipart::Field,
+    is_file: bool,
+    form: types::Form,
+) -> Box<Future<Item = (), Error = Error>> {
+    let max = if is_file {
+        form.max_file_size
+    } else {
+        form.max_field_size
+    };
 
     Box::new(
         field
             .from_err()
-            .fold(BytesMut::new(), move |mut acc, bytes| {
-                if acc.len() + bytes.len() < form.max_field_size {
-                    acc.extend(bytes);
-                    Ok(acc)
+            .fold(0, move |size, bytes| {
+                let size = size + bytes.len();
+
+                if size < max {
+                    Ok(size)
+                } else if is_file {
+                    Err(Error::FileSize)
                 } else {
                     Err(Error::FieldSize)
                 }
             })
-            .and_then(move |bytes| match term {
-                types::FieldTerminator::Bytes => Ok(MultipartContent::Bytes(bytes.freeze())),
-                _ => String::from_utf8(bytes.to_vec())
-                    .map_err(Error::ParseField)
-                    .map(MultipartContent::Text),
-            })
-            .and_then(move |content| {
-                trace!("Matching: {:?}", content);
-                match content {
-                    types::MultipartContent::Text(string) => match term2 {
-                        types::FieldTerminator::File(_) => Err(Error::FieldType),
-                        types::FieldTerminator::Bytes => Err(Error::FieldType),
-                        types::FieldTerminator::Float => string
-                            .parse::<f64>()
-                            .map(MultipartContent::Float)
-                            .map_err(Error::ParseFloat),
-                        types::FieldTerminator::Int => string
-                            .parse::<i64>()
-                            .map(MultipartContent::Int)
-                            .map_err(Error::ParseInt),
-                        types::FieldTerminator::Text => Ok(MultipartContent::Text(string)),
-                    },
-                    b @ types::MultipartContent::Bytes(_) => Ok(b),
-                    _ => Err(Error::FieldType),
+            .map(|_| ()),
+    )
+}
+
+fn is_mixed(field: &actix_multipart::Field) -> bool {
+    let content_type = field.content_type();
+
+    content_type.type_() == mime::MULTIPART && content_type.subtype() == "mixed"
+}
+
+/// Prefer an error from the outer stream that the nested parser couldn't pass on
+fn outer_error(outer: &RefCell<Option<MultipartError>>, e: Error) -> Error {
+    match outer.borrow_mut().take() {
+        Some(outer) => Error::Multipart(outer),
+        None => e,
+    }
+}
+
+/// Handle an embedded `multipart/mixed` part, as sent by RFC 2388 clients for several files
+/// under one name
+///
+/// Each sub-part is stored as an element of the array at the field's path. Sub-parts that are
+/// multipart themselves are rejected rather than stored as opaque files.
+fn handle_mixed(
+    field: actix_multipart::Field,
+    name: Vec<NamePart>,
+    term: types::FieldTerminator,
+    request: Option<HttpRequest>,
+    form: types::Form,
+) -> Box<Stream<Item = Option<MultipartHash>, Error = Error>> {
+    let gen = match term {
+        types::FieldTerminator::File(gen) => gen,
+        types::FieldTerminator::Skip => {
+            return Box::new(skip_field(field, true, form).map(|_| None).into_stream());
+        }
+        _ => return Box::new(result(Err(Error::FieldType)).into_stream()),
+    };
+
+    // The nested parser only passes on payload errors, so any other error from the outer
+    // stream is kept here and reported in place of the one the nested parser gives
+    let outer: Rc<RefCell<Option<MultipartError>>> = Rc::new(RefCell::new(None));
+
+    let headers = field.headers().clone();
+    let stash = Rc::clone(&outer);
+    let body = field.map_err(move |e| match e {
+        MultipartError::Payload(e) => e,
+        e => {
+            *stash.borrow_mut() = Some(e);
+            PayloadError::Incomplete(None)
+        }
+    });
+
+    let part_outer = Rc::clone(&outer);
+    let path = name.clone();
+
+    Box::new(
+        actix_multipart::Multipart::new(&headers, body)
+            .map_err(move |e| outer_error(&outer, Error::from(e)))
+            .and_then(move |part| {
+                if part.content_type().type_() == mime::MULTIPART {
+                    return Either::A(result(Err(Error::ContentType)));
                 }
-            }),
+
+                let filename = parse_content_disposition(&part).filename;
+                let outer = Rc::clone(&part_outer);
+
+                Either::B(
+                    handle_file_upload(
+                        part,
+                        Arc::clone(&gen),
+                        &path,
+                        filename,
+                        request.as_ref(),
+                        form.clone(),
+                    )
+                    .map_err(move |e| outer_error(&outer, e)),
+                )
+            })
+            .map(move |content| Some((name.clone(), content))),
     )
 }
 
 fn handle_stream_field(
     field: actix_multipart::Field,
+    form_charset: Rc<RefCell<Option<String>>>,
+    request: Option<HttpRequest>,
     form: types::Form,
-) -> Box<Future<Item = MultipartHash, Error = Error>> {
+) -> Box<Stream<Item = Option<MultipartHash>, Error = Error>> {
     let content_disposition = parse_content_disposition(&field);
 
-    let name = match content_disposition.name {
+    let field_name = match content_disposition.name {
         Some(name) => name,
-        None => return Box::new(result(Err(Error::Field))),
+        None => return Box::new(result(Err(Error::Field)).into_stream()),
     };
 
-    let name = match parse_multipart_name(name) {
+    // RFC 7578 section 4.6
+    if field_name == "_charset_" {
+        return Box::new(
+            read_charset(field, form_charset, form)
+                .map(|_| None)
+                .into_stream(),
+        );
+    }
+
+    let mut name = match form.parse_name(&field_name) {
         Ok(name) => name,
-        Err(e) => return Box::new(result(Err(e))),
+        Err(e) => return Box::new(result(Err(e)).into_stream()),
     };
 
-    let term = match form.valid_field(name.iter().cloned().collect()) {
-        Some(term) => term,
-        None => return Box::new(result(Err(Error::FieldType))),
+    if is_mixed(&field) {
+        name.push(NamePart::Array);
+
+        return match form.valid_field(name) {
+            Some((name, term)) => handle_mixed(field, name, term, request, form),
+            None => Box::new(result(Err(Error::FieldType)).into_stream()),
+        };
+    }
+
+    let (name, term) = match form.valid_field(name) {
+        Some(found) => found,
+        None => return Box::new(result(Err(Error::FieldType)).into_stream()),
     };
 
     let fut = match term {
+        types::FieldTerminator::Skip => {
+            trace!("Skipping unknown field {}", field_name);
+            let is_file = content_disposition.filename.is_some();
+
+            return Box::new(skip_field(field, is_file, form).map(|_| None).into_stream());
+        }
         types::FieldTerminator::File(gen) => Either::A(handle_file_upload(
             field,
             gen,
+            &name,
             content_disposition.filename,
+            request.as_ref(),
+            form,
+        )),
+        term => Either::B(handle_form_data(
+            field,
+            term,
+            field_name,
+            form_charset,
             form,
         )),
-        term => Either::B(handle_form_data(field, term, form)),
     };
 
-    Box::new(fut.map(|content| (name, content)))
+    Box::new(fut.map(|content| Some((name, content))).into_stream())
 }
 
 fn handle_stream(
     m: actix_multipart::Multipart,
+    request: Option<HttpRequest>,
     form: types::Form,
-) -> Box<Stream<Item = MultipartHash, Error = Error>> {
+) -> Box<Stream<Item = Option<MultipartHash>, Error = Error>> {
+    // Set by a _charset_ field, for the fields that come after it
+    let form_charset = Rc::new(RefCell::new(None));
+
     Box::new(
         m.map_err(Error::from)
             .map(move |field| {
-                handle_stream_field(field, form.clone())
-                    .map(From::from)
-                    .into_stream()
+                handle_stream_field(
+                    field,
+                    Rc::clone(&form_charset),
+                    request.clone(),
+                    form.clone(),
+                )
             })
             .flatten(),
     )
 }
 
+fn remove_files(files: Vec<PathBuf>) -> impl Future<Item = (), Error = ()> {
+    actix_threadpool::run(move || {
+        for file in files {
+            if let Err(e) = fs::remove_file(&file) {
+                warn!("Failed to remove {:?}, {}", file, e);
+            }
+        }
+
+        Ok(()) as Result<(), ()>
+    })
+    .map_err(|_| ())
+}
+
+fn validate(
+    form: types::Form,
+    value: Value,
+    created: HashSet<PathBuf>,
+) -> Box<Future<Item = Value, Error = Error>> {
+    let checked = form.check_keys(&value);
+
+    if checked.is_ok() && form.validators().is_empty() {
+        return Box::new(ok(value));
+    }
+
+    let mut files = Vec::new();
+    value.stored_files(&mut files);
+    files.retain(|file| created.contains(file));
+    files.sort();
+    files.dedup();
+
+    let fut = form.validators().iter().fold(
+        Box::new(result(checked.map(move |_| value))) as Box<Future<Item = Value, Error = Error>>,
+        |fut, validator| {
+            let validator = Arc::clone(validator);
+            Box::new(fut.and_then(move |value| (*validator)(value)))
+        },
+    );
+
+    Box::new(fut.or_else(move |e| remove_files(files).then(move |_| Err(e))))
+}
+
+/// Buffer a request body, failing if it grows beyond `limit` bytes
+pub(crate) fn read_body<S>(body: S, limit: usize) -> Box<Future<Item = BytesMut, Error = Error>>
+where
+    S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    Box::new(
+        body.from_err()
+            .fold(BytesMut::new(), move |mut acc, bytes| {
+                if acc.len() + bytes.len() <= limit {
+                    acc.extend(bytes);
+                    Ok(acc)
+                } else {
+                    Err(Error::BodySize)
+                }
+            }),
+    )
+}
+
+/// Assemble the parsed fields into a `Value`, fill in defaults, and run the form's validators
+///
+/// Stored files are removed if any of this fails.
+pub(crate) fn finish(
+    form: types::Form,
+    mf: MultipartForm,
+) -> Box<Future<Item = Value, Error = Error>> {
+    let created = created_files(&mf);
+
+    match consolidate(mf, form.duplicates(), &created) {
+        Ok((value, ref discarded)) if discarded.is_empty() => complete(form, value, created),
+        Ok((value, discarded)) => {
+            Box::new(remove_files(discarded).then(move |_| complete(form, value, created)))
+        }
+        Err(e) => Box::new(remove_files(created.into_iter().collect()).then(move |_| Err(e))),
+    }
+}
+
+/// Fill in defaults for an assembled `Value` and run the form's validators
+///
+/// Of the files in the `Value`, only those in `created` are removed if validation fails.
+pub(crate) fn complete(
+    form: types::Form,
+    mut value: Value,
+    created: HashSet<PathBuf>,
+) -> Box<Future<Item = Value, Error = Error>> {
+    form.fill_defaults(&mut value);
+    validate(form, value, created)
+}
+
 /// Handle multipart streams from Actix Web
 pub fn handle_multipart(
     m: actix_multipart::Multipart,
     form: types::Form,
 ) -> Box<Future<Item = Value, Error = Error>> {
+    multipart(m, None, form)
+}
+
+/// Handle multipart streams from Actix Web, making the request available to filename generators
+///
+/// Generators can reach the request through `UploadContext::request`, to name files after
+/// something in its headers or extensions, like the id of an authenticated user.
+///
+/// # Example
+/// ```rust
+/// # use actix_multipart::Multipart;
+/// # use actix_web::{web::Data, HttpRequest};
+/// # use form_data::{handle_multipart_request, Error, Form, Value};
+/// # use futures::Future;
+/// fn upload(
+///     req: HttpRequest,
+///     mp: Multipart,
+///     form: Data<Form>,
+/// ) -> Box<Future<Item = Value, Error = Error>> {
+///     handle_multipart_request(mp, &req, form.get_ref().clone())
+/// }
+/// ```
+pub fn handle_multipart_request(
+    m: actix_multipart::Multipart,
+    req: &HttpRequest,
+    form: types::Form,
+) -> Box<Future<Item = Value, Error = Error>> {
+    multipart(m, Some(req.clone()), form)
+}
+
+fn multipart(
+    m: actix_multipart::Multipart,
+    request: Option<HttpRequest>,
+    form: types::Form,
+) -> Box<Future<Item = Value, Error = Error>> {
+    let form2 = form.clone();
+
     Box::new(
-        handle_stream(m, form.clone())
+        handle_stream(m, request, form.clone())
             .fold(
                 (Vec::new(), 0, 0),
-                move |(mut acc, file_count, field_count), (name, content)| match content {
-                    MultipartContent::File {
-                        filename,
-                        stored_as,
-                    } => {
+                move |(mut acc, file_count, field_count), hash| match hash {
+                    Some((name, file @ MultipartContent::File { .. })) => {
                         let file_count = file_count + 1;
 
                         if file_count < form.max_files {
-                            acc.push((
-                                name,
-                                MultipartContent::File {
-                                    filename,
-                                    stored_as,
-                                },
-                            ));
+                            acc.push((name, file));
 
                             Ok((acc, file_count, field_count))
                         } else {
                             Err(Error::FileCount)
                         }
                     }
-                    b @ MultipartContent::Bytes(_)
-                    | b @ MultipartContent::Text(_)
-                    | b @ MultipartContent::Float(_)
-                    | b @ MultipartContent::Int(_) => {
+                    b => {
                         let field_count = field_count + 1;
 
                         if field_count < form.max_fields {
-                            acc.push((name, b));
+                            // Skipped fields count towards the limit, but aren't kept
+                            acc.extend(b);
 
                             Ok((acc, file_count, field_count))
                         } else {
@@ -317,6 +1057,248 @@ pub fn handle_multipart(
                     }
                 },
             )
-            .map(|(multipart_form, _, _)| consolidate(multipart_form)),
+            .and_then(move |(multipart_form, _, _)| finish(form2, multipart_form)),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn file(name: &str, stored_as: &str) -> MultipartHash {
+        stored(name, stored_as, false)
+    }
+
+    fn deduplicated(name: &str, stored_as: &str) -> MultipartHash {
+        stored(name, stored_as, true)
+    }
+
+    fn stored(name: &str, stored_as: &str, deduplicated: bool) -> MultipartHash {
+        (
+            vec![NamePart::Map(name.to_owned())],
+            MultipartContent::File {
+                filename: "a.txt".to_owned(),
+                stored_as: PathBuf::from(stored_as),
+                size: 0,
+                deduplicated,
+            },
+        )
+    }
+
+    fn assemble(
+        mf: MultipartForm,
+        duplicates: DuplicateFields,
+    ) -> Result<(Value, Vec<PathBuf>), Error> {
+        let created = created_files(&mf);
+
+        consolidate(mf, duplicates, &created)
+    }
+
+    /// Parse names like `items[][name]`, keeping only what grouping cares about
+    fn text(name: &str, value: &str) -> MultipartHash {
+        let path = name
+            .split('[')
+            .map(|part| match part.trim_end_matches(']') {
+                "" => NamePart::Array,
+                key => NamePart::Map(key.to_owned()),
+            })
+            .collect();
+
+        (path, MultipartContent::Text(value.to_owned()))
+    }
+
+    fn record(fields: &[(&str, Value)]) -> Value {
+        Value::Map(
+            fields
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), value.clone()))
+                .collect(),
+        )
+    }
+
+    fn grouped(mf: MultipartForm) -> Value {
+        let (value, _) = assemble(mf, DuplicateFields::KeepFirst).unwrap();
+
+        value.get("items").unwrap().clone()
+    }
+
+    #[test]
+    fn appended_records() {
+        let mf = vec![
+            text("items[][name]", "a"),
+            text("items[][qty]", "1"),
+            text("items[][name]", "b"),
+            text("items[][qty]", "2"),
+        ];
+
+        assert_eq!(
+            grouped(mf),
+            Value::Array(vec![
+                record(&[
+                    ("name", Value::Text("a".to_owned())),
+                    ("qty", Value::Text("1".to_owned())),
+                ]),
+                record(&[
+                    ("name", Value::Text("b".to_owned())),
+                    ("qty", Value::Text("2".to_owned())),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn appended_arrays_stay_in_their_record() {
+        let mf = vec![
+            text("items[][name]", "a"),
+            text("items[][tags][]", "x"),
+            text("items[][tags][]", "y"),
+        ];
+
+        assert_eq!(
+            grouped(mf),
+            Value::Array(vec![record(&[
+                ("name", Value::Text("a".to_owned())),
+                (
+                    "tags",
+                    Value::Array(vec![
+                        Value::Text("x".to_owned()),
+                        Value::Text("y".to_owned()),
+                    ]),
+                ),
+            ])])
+        );
+    }
+
+    #[test]
+    fn repeated_keys_start_a_record() {
+        let mf = vec![text("items[][name]", "a"), text("items[][name]", "b")];
+
+        assert_eq!(
+            grouped(mf),
+            Value::Array(vec![
+                record(&[("name", Value::Text("a".to_owned()))]),
+                record(&[("name", Value::Text("b".to_owned()))]),
+            ])
+        );
+    }
+
+    #[test]
+    fn discarded_files() {
+        let cases = vec![
+            (DuplicateFields::KeepFirst, "one", "two"),
+            (DuplicateFields::KeepLast, "two", "one"),
+        ];
+
+        for (duplicates, kept, removed) in cases {
+            let mf = vec![file("f", "one"), file("f", "two")];
+            let (value, discarded) = assemble(mf, duplicates).unwrap();
+
+            let mut files = Vec::new();
+            value.stored_files(&mut files);
+
+            assert_eq!(files, vec![PathBuf::from(kept)], "{:?}", duplicates);
+            assert_eq!(discarded, vec![PathBuf::from(removed)], "{:?}", duplicates);
+        }
+
+        let mf = vec![file("f", "one"), file("f", "two")];
+        let (_, discarded) = assemble(mf, DuplicateFields::Array).unwrap();
+        assert!(discarded.is_empty());
+
+        let mf = vec![file("f", "one"), file("f", "two")];
+        match assemble(mf, DuplicateFields::Reject) {
+            Err(Error::DuplicateField(ref name)) => assert_eq!(name, "f"),
+            _ => panic!("expected a duplicate field error"),
+        }
+    }
+
+    #[test]
+    fn deduplicated_files_are_kept() {
+        // Identical uploads share a file, which one of them created
+        for &duplicates in &[DuplicateFields::KeepFirst, DuplicateFields::KeepLast] {
+            let mf = vec![file("f", "one"), deduplicated("f", "one")];
+            let (value, discarded) = assemble(mf, duplicates).unwrap();
+
+            assert_eq!(
+                value.get("f").and_then(Value::as_file).unwrap().1,
+                Path::new("one")
+            );
+            assert!(discarded.is_empty(), "{:?}", duplicates);
+        }
+
+        // A file from an earlier request is never removed
+        let mf = vec![file("f", "one"), deduplicated("f", "two")];
+        let (_, discarded) = assemble(mf, DuplicateFields::KeepFirst).unwrap();
+        assert!(discarded.is_empty());
+
+        let mf = vec![deduplicated("f", "one"), file("f", "two")];
+        let (_, discarded) = assemble(mf, DuplicateFields::KeepLast).unwrap();
+        assert!(discarded.is_empty());
+
+        let mf = vec![file("f", "one"), deduplicated("g", "two")];
+        let created = created_files(&mf);
+        assert_eq!(created.len(), 1);
+        assert!(created.contains(Path::new("one")));
+    }
+
+    #[test]
+    fn shape_conflicts() {
+        let cases = vec![
+            vec![
+                file("user", "one"),
+                (
+                    vec![
+                        NamePart::Map("user".to_owned()),
+                        NamePart::Map("name".to_owned()),
+                    ],
+                    MultipartContent::Text("a".to_owned()),
+                ),
+            ],
+            vec![
+                (
+                    vec![NamePart::Map("tags".to_owned()), NamePart::Array],
+                    MultipartContent::Text("a".to_owned()),
+                ),
+                (
+                    vec![
+                        NamePart::Map("tags".to_owned()),
+                        NamePart::Map("b".to_owned()),
+                    ],
+                    MultipartContent::Text("b".to_owned()),
+                ),
+            ],
+        ];
+
+        for mf in cases {
+            for &duplicates in &[DuplicateFields::KeepFirst, DuplicateFields::KeepLast] {
+                match assemble(mf.clone(), duplicates) {
+                    Err(Error::FieldShape(_)) => (),
+                    _ => panic!("expected a field shape error"),
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn hashed_files_dont_replace_existing_ones() {
+        let dir = std::env::temp_dir().join(format!("form-data-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let temporary = dir.join(".1.part.txt");
+        let renamed = dir.join("abc.txt");
+
+        fs::write(&temporary, "new").unwrap();
+        assert!(!move_hashed(&temporary, &renamed).unwrap());
+        assert!(!temporary.exists());
+        assert_eq!(fs::read_to_string(&renamed).unwrap(), "new");
+
+        fs::write(&temporary, "newer").unwrap();
+        assert!(move_hashed(&temporary, &renamed).unwrap());
+        assert!(!temporary.exists());
+        assert_eq!(fs::read_to_string(&renamed).unwrap(), "new");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use actix_web::error::PayloadError;
+use bytes::Bytes;
+use futures::{Future, Stream};
+use log::trace;
+use url::form_urlencoded;
+
+use crate::{
+    error::Error,
+    types::{FieldTerminator, Form, MultipartForm, Value},
+    upload::{finish, read_body},
+};
+
+fn parse_pairs(form: &Form, body: &[u8]) -> Result<MultipartForm, Error> {
+    let mut mf = Vec::new();
+    let mut field_count = 0;
+
+    for (name, value) in form_urlencoded::parse(body) {
+        field_count += 1;
+
+        if field_count >= form.max_fields {
+            return Err(Error::FieldCount);
+        }
+
+        if value.len() >= form.max_field_size {
+            return Err(Error::FieldSize);
+        }
+
+        let (name_parts, term) = form
+            .parse_name(&name)
+            .and_then(|name_parts| form.valid_field(name_parts).ok_or(Error::FieldType))?;
+
+        if let FieldTerminator::Skip = term {
+            trace!("Skipping unknown field {}", name);
+            continue;
+        }
+
+        let content = term.parse_text(&name, value.into_owned())?;
+
+        mf.push((name_parts, content));
+    }
+
+    Ok(mf)
+}
+
+/// Handle `application/x-www-form-urlencoded` bodies with the same `Form` used for multipart
+///
+/// Fields are named, validated, and limited just like multipart fields, and the result is the
+/// same `Value`. File fields can't be submitted this way. Since every field is limited to
+/// `max_field_size` bytes, bodies larger than `max_fields` times `max_field_size` are rejected
+/// before parsing.
+///
+/// # Example
+/// ```rust
+/// # use actix_web::web::Payload;
+/// # use form_data::{handle_urlencoded, Error, Form, Value};
+/// # use futures::Future;
+/// fn submit(body: Payload, form: Form) -> Box<Future<Item = Value, Error = Error>> {
+///     handle_urlencoded(body, form)
+/// }
+/// ```
+pub fn handle_urlencoded<S>(body: S, form: Form) -> Box<Future<Item = Value, Error = Error>>
+where
+    S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    Box::new(
+        read_body(body, form.max_body_size())
+            .and_then(move |bytes| parse_pairs(&form, &bytes).map(|mf| (form, mf)))
+            .and_then(|(form, mf)| finish(form, mf)),
+    )
+}